@@ -0,0 +1,32 @@
+//! Precompresses the embedded static assets, so serving them compressed does
+//! no work per request.
+
+use std::{env, fs, io::Write, path::Path};
+
+/// Static assets in `src/` served with a content coding.
+const ASSETS: &[&str] = &["main.css"];
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    for asset in ASSETS {
+        let source = Path::new("src").join(asset);
+        println!("cargo:rerun-if-changed={}", source.display());
+        let data = fs::read(&source).unwrap();
+
+        let out = |extension: &str, compressed: Vec<u8>| {
+            let path = Path::new(&out_dir).join(format!("{asset}.{extension}"));
+            fs::write(path, compressed).unwrap();
+        };
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gzip.write_all(&data).unwrap();
+        out("gz", gzip.finish().unwrap());
+
+        let mut brotli = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+        brotli.write_all(&data).unwrap();
+        out("br", brotli.into_inner());
+
+        out("zst", zstd::encode_all(data.as_slice(), 19).unwrap());
+    }
+}
@@ -19,10 +19,10 @@
           ./rust-toolchain.toml);
 
         ip-info = let
-          # Askama templates and CSS live outside Cargo's source tree, so the
+          # Askama templates, CSS and the User-Agent rules live outside Cargo's source tree, so the
           # default Cargo source filter would strip them from the build sandbox.
           assetFilter = path: _type:
-            builtins.match ".*(css|html)$" path != null;
+            builtins.match ".*(css|html|yaml)$" path != null;
           assetOrCargo = path: type:
             (assetFilter path type) || (craneLib.filterCargoSources path type);
         in craneLib.buildPackage {
@@ -0,0 +1,423 @@
+//! Version 1 of the JSON API, served under `/api/v1`.
+//!
+//! The response types here are the API contract. They are separate from
+//! [`IpResponse`], which also feeds the HTML template and the negotiated
+//! formats at `/`, and are only built through explicit conversions, so
+//! changes to the page cannot silently change the API. Every response carries
+//! a `schema_version`; fields are never removed or renamed within a version,
+//! and optional values are `null` rather than left out.
+//!
+//! | Route                 | Response           |
+//! |-----------------------|--------------------|
+//! | `/api/v1/ip`          | [`IpInfo`]         |
+//! | `/api/v1/headers`     | [`Headers`]        |
+//! | `/api/v1/user-agent`  | [`UserAgentReport`] |
+//!
+//! In the OpenAPI document, the schemas are prefixed with the version, such as
+//! `v1.IpInfo`.
+//!
+//! The unversioned JSON at `/` is deprecated; see [`deprecate_unversioned()`].
+//!
+//! [`IpResponse`]: crate::handle_index::IpResponse
+
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::client_hints;
+use crate::config::Config;
+use crate::handle_index::{parse_structured_headers, parse_user_agent, real_ip, used_headers_axum};
+use crate::user_agent;
+
+/// The schema version of every response in this module.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// When the unversioned JSON at `/` was deprecated, as an RFC 9745 date.
+pub const UNVERSIONED_DEPRECATION: &str = "@1792368000";
+
+/// When the unversioned JSON at `/` may stop working, as an HTTP date.
+pub const UNVERSIONED_SUNSET: &str = "Sat, 01 May 2027 00:00:00 GMT";
+
+/// Adds the `Deprecation`, `Sunset` and `Link` headers announcing that the
+/// unversioned JSON is replaced by `/api/v1/ip`.
+pub fn deprecate_unversioned(headers: &mut HeaderMap) {
+    headers.insert(
+        HeaderName::from_static("deprecation"),
+        HeaderValue::from_static(UNVERSIONED_DEPRECATION),
+    );
+    headers.insert(
+        HeaderName::from_static("sunset"),
+        HeaderValue::from_static(UNVERSIONED_SUNSET),
+    );
+    headers.append(
+        axum::http::header::LINK,
+        HeaderValue::from_static("</api/v1/ip>; rel=\"successor-version\""),
+    );
+}
+
+/// Everything known about the client.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::IpInfo)]
+pub struct IpInfo {
+    pub schema_version: u32,
+    pub ip: String,
+    /// The visible request headers, by lowercase name
+    pub headers: BTreeMap<String, String>,
+    #[schema(required = true)]
+    pub user_agent: Option<UserAgent>,
+    #[schema(required = true)]
+    pub client_hints: Option<ClientHints>,
+    /// Visible headers that are known structured fields, in the canonical
+    /// serialization of RFC 8941
+    pub structured_headers: BTreeMap<String, String>,
+}
+
+/// The visible request headers.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::Headers)]
+pub struct Headers {
+    pub schema_version: u32,
+    pub headers: BTreeMap<String, String>,
+}
+
+/// What the client says about itself.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::UserAgentReport)]
+pub struct UserAgentReport {
+    pub schema_version: u32,
+    #[schema(required = true)]
+    pub user_agent: Option<UserAgent>,
+    #[schema(required = true)]
+    pub client_hints: Option<ClientHints>,
+}
+
+/// A parsed `User-Agent` header.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::UserAgent)]
+pub struct UserAgent {
+    pub browser: Software,
+    pub os: Software,
+    pub device: Device,
+    pub is_bot: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::Software)]
+pub struct Software {
+    pub family: String,
+    #[schema(required = true)]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::Device)]
+pub struct Device {
+    pub family: String,
+    #[schema(required = true)]
+    pub brand: Option<String>,
+    #[schema(required = true)]
+    pub model: Option<String>,
+    /// One of `desktop`, `mobile`, `tablet`, `bot` or `other`
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// The User-Agent Client Hints sent with the request.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::ClientHints)]
+pub struct ClientHints {
+    #[schema(required = true)]
+    pub brands: Option<Vec<Brand>>,
+    #[schema(required = true)]
+    pub full_version_list: Option<Vec<Brand>>,
+    #[schema(required = true)]
+    pub mobile: Option<bool>,
+    #[schema(required = true)]
+    pub platform: Option<String>,
+    #[schema(required = true)]
+    pub platform_version: Option<String>,
+    #[schema(required = true)]
+    pub arch: Option<String>,
+    #[schema(required = true)]
+    pub bitness: Option<String>,
+    #[schema(required = true)]
+    pub model: Option<String>,
+    #[schema(required = true)]
+    pub wow64: Option<bool>,
+    #[schema(required = true)]
+    pub form_factors: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[schema(as = v1::Brand)]
+pub struct Brand {
+    pub brand: String,
+    pub version: String,
+}
+
+impl From<user_agent::Software> for Software {
+    fn from(software: user_agent::Software) -> Self {
+        Software {
+            family: software.family,
+            version: software.version,
+        }
+    }
+}
+
+impl From<user_agent::UserAgentInfo> for UserAgent {
+    fn from(info: user_agent::UserAgentInfo) -> Self {
+        UserAgent {
+            browser: info.browser.into(),
+            os: info.os.into(),
+            device: Device {
+                family: info.device.family,
+                brand: info.device.brand,
+                model: info.device.model,
+                kind: info.device.kind.to_string(),
+            },
+            is_bot: info.is_bot,
+        }
+    }
+}
+
+impl From<client_hints::Brand> for Brand {
+    fn from(brand: client_hints::Brand) -> Self {
+        Brand {
+            brand: brand.brand,
+            version: brand.version,
+        }
+    }
+}
+
+impl From<client_hints::ClientHints> for ClientHints {
+    fn from(hints: client_hints::ClientHints) -> Self {
+        let brands = |brands: Option<Vec<client_hints::Brand>>| {
+            brands.map(|brands| brands.into_iter().map(Brand::from).collect())
+        };
+
+        ClientHints {
+            brands: brands(hints.brands),
+            full_version_list: brands(hints.full_version_list),
+            mobile: hints.mobile,
+            platform: hints.platform,
+            platform_version: hints.platform_version,
+            arch: hints.arch,
+            bitness: hints.bitness,
+            model: hints.model,
+            wow64: hints.wow64,
+            form_factors: hints.form_factors,
+        }
+    }
+}
+
+/// Builds the [`IpInfo`] of a request from the client address `ip`.
+pub fn ip_info(config: &Config, headers: &HeaderMap, ip: String) -> IpInfo {
+    IpInfo {
+        schema_version: SCHEMA_VERSION,
+        ip,
+        headers: used_headers_axum(headers, &config.header_policy),
+        user_agent: parse_user_agent(config, headers).map(UserAgent::from),
+        client_hints: client_hints(config, headers),
+        structured_headers: parse_structured_headers(config, headers)
+            .into_iter()
+            .map(|(name, field)| (name, field.to_string()))
+            .collect(),
+    }
+}
+
+fn client_hints(config: &Config, headers: &HeaderMap) -> Option<ClientHints> {
+    client_hints::ClientHints::from_headers(headers, |name| {
+        config.header_policy.reveals_value(name)
+    })
+    .map(ClientHints::from)
+}
+
+/// Shows everything known about the client
+#[utoipa::path(
+    get,
+    path = "/api/v1/ip",
+    tag = "api/v1",
+    responses((status = 200, description = "The client's IP address and request details", body = IpInfo))
+)]
+pub async fn handle_ip(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Json<IpInfo> {
+    Json(ip_info(&config, &headers, real_ip(&headers, addr.ip())))
+}
+
+/// Shows the visible request headers
+#[utoipa::path(
+    get,
+    path = "/api/v1/headers",
+    tag = "api/v1",
+    responses((status = 200, description = "The visible request headers", body = Headers))
+)]
+pub async fn handle_headers(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+) -> Json<Headers> {
+    Json(Headers {
+        schema_version: SCHEMA_VERSION,
+        headers: used_headers_axum(&headers, &config.header_policy),
+    })
+}
+
+/// Shows what the client says about itself
+#[utoipa::path(
+    get,
+    path = "/api/v1/user-agent",
+    tag = "api/v1",
+    responses((status = 200, description = "The parsed User-Agent and client hints", body = UserAgentReport))
+)]
+pub async fn handle_user_agent(
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+) -> Json<UserAgentReport> {
+    Json(UserAgentReport {
+        schema_version: SCHEMA_VERSION,
+        user_agent: parse_user_agent(&config, &headers).map(UserAgent::from),
+        client_hints: client_hints(&config, &headers),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle_index::handle_index;
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{header, StatusCode},
+        response::Response,
+        routing::get,
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", get(handle_index))
+            .route("/api/v1/ip", get(handle_ip))
+            .route("/api/v1/headers", get(handle_headers))
+            .route("/api/v1/user-agent", get(handle_user_agent))
+            .with_state(Arc::new(Config::default()))
+    }
+
+    async fn request(uri: &str, accept: &'static str) -> Response {
+        let mut request = Request::builder()
+            .uri(uri)
+            .header(header::ACCEPT, accept)
+            .header(header::USER_AGENT, "curl/8.0")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4711))));
+        app().oneshot(request).await.unwrap()
+    }
+
+    async fn json(response: Response) -> Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn is_deprecated(response: &Response) -> bool {
+        let headers = response.headers();
+        let deprecated = headers.contains_key("deprecation");
+        assert_eq!(headers.contains_key("sunset"), deprecated);
+        assert_eq!(headers.contains_key(header::LINK), deprecated);
+        deprecated
+    }
+
+    #[tokio::test]
+    async fn test_v1_routes() {
+        let response = request("/api/v1/ip", "*/*").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!is_deprecated(&response));
+        let body = json(response).await;
+        assert_eq!(body["schema_version"], SCHEMA_VERSION);
+        assert_eq!(body["ip"], "192.0.2.1");
+        assert_eq!(body["headers"]["user-agent"], "curl/8.0");
+        assert_eq!(body["user_agent"]["browser"]["family"], "curl");
+        assert!(body["client_hints"].is_null());
+
+        let response = request("/api/v1/headers", "text/html").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!is_deprecated(&response));
+        let body = json(response).await;
+        assert_eq!(body["schema_version"], SCHEMA_VERSION);
+        assert_eq!(body["headers"]["accept"], "text/html");
+
+        let response = request("/api/v1/user-agent", "*/*").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!is_deprecated(&response));
+        let body = json(response).await;
+        assert_eq!(body["schema_version"], SCHEMA_VERSION);
+        assert_eq!(body["user_agent"]["browser"]["version"], "8.0");
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_json_deprecated() {
+        let response = request("/", "application/json").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(is_deprecated(&response));
+        let headers = response.headers();
+        assert_eq!(headers["deprecation"], UNVERSIONED_DEPRECATION);
+        assert_eq!(headers["sunset"], UNVERSIONED_SUNSET);
+        assert_eq!(
+            headers[header::LINK],
+            "</api/v1/ip>; rel=\"successor-version\""
+        );
+
+        let response = request("/?format=json", "text/html").await;
+        assert!(is_deprecated(&response));
+
+        let response = request("/", "text/html").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!is_deprecated(&response));
+
+        let response = request("/", "text/plain").await;
+        assert!(!is_deprecated(&response));
+    }
+
+    #[test]
+    fn test_schema_keeps_null_fields() {
+        let report = UserAgentReport {
+            schema_version: SCHEMA_VERSION,
+            user_agent: Some(UserAgent {
+                browser: Software {
+                    family: "curl".to_string(),
+                    version: Some("8.0".to_string()),
+                },
+                os: Software {
+                    family: "Other".to_string(),
+                    version: None,
+                },
+                device: Device {
+                    family: "Other".to_string(),
+                    brand: None,
+                    model: None,
+                    kind: "other".to_string(),
+                },
+                is_bot: false,
+            }),
+            client_hints: Some(client_hints::ClientHints::default().into()),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"schema_version":1,"user_agent":{"browser":{"family":"curl","version":"8.0"},"os":{"family":"Other","version":null},"device":{"family":"Other","brand":null,"model":null,"type":"other"},"is_bot":false},"client_hints":{"brands":null,"full_version_list":null,"mobile":null,"platform":null,"platform_version":null,"arch":null,"bitness":null,"model":null,"wow64":null,"form_factors":null}}"#
+        );
+    }
+}
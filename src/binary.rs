@@ -0,0 +1,154 @@
+//! Binary representations for constrained clients: CBOR and MessagePack.
+//!
+//! Both formats share the CBOR data model. Values are first serialized into a
+//! [`ciborium::Value`] tree in non-human-readable mode, so types can choose a
+//! compact binary form; see [`ip_address()`]. MessagePack has no tags, so
+//! tagged values are written without their tag.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::binary;
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Response {
+//!     #[serde(serialize_with = "binary::ip_address")]
+//!     ip: String,
+//! }
+//!
+//! let response = Response { ip: "192.0.2.1".to_string() };
+//!
+//! // {"ip": 52(h'C0000201')}
+//! assert_eq!(
+//!     binary::to_cbor(&response).unwrap(),
+//!     b"\xa1\x62ip\xd8\x34\x44\xc0\x00\x02\x01"
+//! );
+//!
+//! // {"ip": bin8 C0000201}
+//! assert_eq!(
+//!     binary::to_msgpack(&response).unwrap(),
+//!     b"\x81\xa2ip\xc4\x04\xc0\x00\x02\x01"
+//! );
+//! ```
+//!
+//! # References
+//!
+//! - [RFC 8949](https://www.rfc-editor.org/rfc/rfc8949) - Concise Binary Object Representation (CBOR)
+//! - [RFC 9164](https://www.rfc-editor.org/rfc/rfc9164) - CBOR Tags for IPv4 and IPv6 Addresses and Prefixes
+//! - [MessagePack specification](https://github.com/msgpack/msgpack/blob/master/spec.md)
+
+use std::net::IpAddr;
+
+use ciborium::{tag::Captured, Value};
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+/// CBOR tag of an IPv4 address (RFC 9164 Section 3).
+pub const TAG_IPV4: u64 = 52;
+/// CBOR tag of an IPv6 address (RFC 9164 Section 3).
+pub const TAG_IPV6: u64 = 54;
+
+/// Errors from [`to_cbor()`] and [`to_msgpack()`].
+#[derive(Error, Debug)]
+pub enum BinaryError {
+    #[error("invalid value: {0}")]
+    Value(#[from] ciborium::value::Error),
+    #[error("CBOR encoding failed: {0}")]
+    Cbor(#[from] ciborium::ser::Error<std::io::Error>),
+    #[error("MessagePack encoding failed: {0}")]
+    MsgPack(#[from] rmp_serde::encode::Error),
+}
+
+/// Serializes a value as CBOR.
+pub fn to_cbor<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, BinaryError> {
+    let value = Value::serialized(value)?;
+    let mut cbor = Vec::new();
+    ciborium::into_writer(&value, &mut cbor)?;
+    Ok(cbor)
+}
+
+/// Serializes a value as MessagePack.
+pub fn to_msgpack<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, BinaryError> {
+    let value = untagged(Value::serialized(value)?);
+    Ok(rmp_serde::to_vec(&value)?)
+}
+
+/// Removes the tags from a CBOR value tree.
+fn untagged(value: Value) -> Value {
+    match value {
+        Value::Tag(_, value) => untagged(*value),
+        Value::Array(items) => Value::Array(items.into_iter().map(untagged).collect()),
+        Value::Map(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (untagged(k), untagged(v)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Serializes an IP address given as text.
+///
+/// Human-readable formats get the text unchanged. Binary formats get the
+/// address as a byte string tagged per RFC 9164, unless the text is not a
+/// valid address.
+pub fn ip_address<S: Serializer>(ip: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        return serializer.serialize_str(ip);
+    }
+
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            Captured(Some(TAG_IPV4), Value::Bytes(ip.octets().to_vec())).serialize(serializer)
+        }
+        Ok(IpAddr::V6(ip)) => {
+            Captured(Some(TAG_IPV6), Value::Bytes(ip.octets().to_vec())).serialize(serializer)
+        }
+        Err(_) => serializer.serialize_str(ip),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Ip(#[serde(serialize_with = "ip_address")] &'static str);
+
+    #[test]
+    fn test_ipv6_tagged() {
+        let mut expected = b"\xd8\x36\x50".to_vec();
+        expected.extend([0x20, 0x01, 0x0d, 0xb8]);
+        expected.extend([0; 11]);
+        expected.push(1);
+        assert_eq!(to_cbor(&Ip("2001:db8::1")).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_invalid_ip_as_text() {
+        assert_eq!(to_cbor(&Ip("unknown")).unwrap(), b"\x67unknown");
+        assert_eq!(to_msgpack(&Ip("unknown")).unwrap(), b"\xa7unknown");
+    }
+
+    #[test]
+    fn test_human_readable_unchanged() {
+        assert_eq!(
+            serde_json::to_string(&Ip("192.0.2.1")).unwrap(),
+            "\"192.0.2.1\""
+        );
+    }
+
+    #[test]
+    fn test_untagged_nested() {
+        let value = Value::Array(vec![Value::Tag(
+            TAG_IPV4,
+            Box::new(Value::Bytes(vec![127, 0, 0, 1])),
+        )]);
+        assert_eq!(
+            untagged(value),
+            Value::Array(vec![Value::Bytes(vec![127, 0, 0, 1])])
+        );
+    }
+}
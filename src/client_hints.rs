@@ -0,0 +1,337 @@
+//! User-Agent Client Hints (`Sec-CH-UA*` request headers).
+//!
+//! Chromium-based browsers send a reduced `User-Agent` string and move the
+//! details into [Client Hints](https://wicg.github.io/ua-client-hints/). The
+//! low-entropy hints are sent on every request; high-entropy hints are only
+//! sent once the server asks for them with `Accept-CH`.
+//!
+//! Hint values are [Structured Field Values](crate::structured_fields); hints
+//! whose value does not have the expected type are ignored.
+//!
+//! # Example
+//!
+//! ```
+//! use axum::http::HeaderMap;
+//! use ip_info::client_hints::ClientHints;
+//!
+//! let mut headers = HeaderMap::new();
+//! headers.insert(
+//!     "sec-ch-ua",
+//!     r#""Chromium";v="124", "Google Chrome";v="124", "Not-A.Brand";v="99""#
+//!         .parse()
+//!         .unwrap(),
+//! );
+//! headers.insert("sec-ch-ua-mobile", "?0".parse().unwrap());
+//! headers.insert("sec-ch-ua-platform", r#""Linux""#.parse().unwrap());
+//!
+//! let hints = ClientHints::from_headers(&headers, |_| true).unwrap();
+//! let brands = hints.brands.unwrap();
+//! assert_eq!(brands[1].brand, "Google Chrome");
+//! assert_eq!(brands[1].version, "124");
+//! assert_eq!(hints.mobile, Some(false));
+//! assert_eq!(hints.platform.as_deref(), Some("Linux"));
+//! ```
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::structured_fields::{parse_item, parse_list, BareItem, Item, Member};
+
+/// High-entropy hints requested from the client via `Accept-CH`.
+pub const HIGH_ENTROPY_HINTS: &[&str] = &[
+    "Sec-CH-UA-Arch",
+    "Sec-CH-UA-Bitness",
+    "Sec-CH-UA-Form-Factors",
+    "Sec-CH-UA-Full-Version-List",
+    "Sec-CH-UA-Model",
+    "Sec-CH-UA-Platform-Version",
+    "Sec-CH-UA-WoW64",
+];
+
+/// High-entropy hints the browser retries the request for if they were not
+/// sent: the details User-Agent reduction removed from the `User-Agent`
+/// string.
+pub const CRITICAL_HINTS: &[&str] = &[
+    "Sec-CH-UA-Full-Version-List",
+    "Sec-CH-UA-Model",
+    "Sec-CH-UA-Platform-Version",
+];
+
+/// The request headers read by [`ClientHints::from_headers()`].
+pub const HEADERS: &[&str] = &[
+    "sec-ch-ua",
+    "sec-ch-ua-full-version-list",
+    "sec-ch-ua-mobile",
+    "sec-ch-ua-platform",
+    "sec-ch-ua-platform-version",
+    "sec-ch-ua-arch",
+    "sec-ch-ua-bitness",
+    "sec-ch-ua-model",
+    "sec-ch-ua-wow64",
+    "sec-ch-ua-form-factors",
+];
+
+lazy_static! {
+    /// The `Accept-CH` response header value.
+    pub static ref ACCEPT_CH: HeaderValue =
+        HeaderValue::from_str(&HIGH_ENTROPY_HINTS.join(", ")).unwrap();
+    /// The `Critical-CH` response header value.
+    pub static ref CRITICAL_CH: HeaderValue =
+        HeaderValue::from_str(&CRITICAL_HINTS.join(", ")).unwrap();
+    /// [`HEADERS`] as header names, for `Vary`.
+    pub static ref HEADER_NAMES: Vec<HeaderName> = HEADERS
+        .iter()
+        .map(|name| HeaderName::from_static(name))
+        .collect();
+}
+
+/// A brand and its version, as listed in `Sec-CH-UA` and
+/// `Sec-CH-UA-Full-Version-List`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Brand {
+    pub brand: String,
+    pub version: String,
+}
+
+/// The User-Agent Client Hints sent with a request.
+///
+/// Each field is `None` if the corresponding header was absent or invalid.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ClientHints {
+    /// `Sec-CH-UA`: brands with their significant versions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brands: Option<Vec<Brand>>,
+    /// `Sec-CH-UA-Full-Version-List`: brands with their full versions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_version_list: Option<Vec<Brand>>,
+    /// `Sec-CH-UA-Mobile`: whether the client prefers a mobile experience
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mobile: Option<bool>,
+    /// `Sec-CH-UA-Platform`: the operating system
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    /// `Sec-CH-UA-Platform-Version`: the operating system version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform_version: Option<String>,
+    /// `Sec-CH-UA-Arch`: the CPU architecture
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    /// `Sec-CH-UA-Bitness`: the CPU bitness
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitness: Option<String>,
+    /// `Sec-CH-UA-Model`: the device model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// `Sec-CH-UA-WoW64`: whether a 32-bit binary runs on 64-bit Windows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wow64: Option<bool>,
+    /// `Sec-CH-UA-Form-Factors`: the device form factors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub form_factors: Option<Vec<String>>,
+}
+
+impl ClientHints {
+    /// Parses the Client Hints in a request.
+    ///
+    /// Only headers for which `visible` returns true are considered, so the
+    /// header policy applies to hints as well. Returns `None` if no hints
+    /// were sent.
+    pub fn from_headers(headers: &HeaderMap, visible: impl Fn(&str) -> bool) -> Option<Self> {
+        let get = |name: &str| {
+            if !visible(name) {
+                return None;
+            }
+            headers.get(name).and_then(|v| v.to_str().ok())
+        };
+
+        let hints = ClientHints {
+            brands: get("sec-ch-ua").and_then(parse_brand_list),
+            full_version_list: get("sec-ch-ua-full-version-list").and_then(parse_brand_list),
+            mobile: get("sec-ch-ua-mobile").and_then(parse_boolean),
+            platform: get("sec-ch-ua-platform").and_then(parse_string),
+            platform_version: get("sec-ch-ua-platform-version").and_then(parse_string),
+            arch: get("sec-ch-ua-arch").and_then(parse_string),
+            bitness: get("sec-ch-ua-bitness").and_then(parse_string),
+            model: get("sec-ch-ua-model").and_then(parse_string),
+            wow64: get("sec-ch-ua-wow64").and_then(parse_boolean),
+            form_factors: get("sec-ch-ua-form-factors").and_then(parse_string_list),
+        };
+
+        if hints == ClientHints::default() {
+            None
+        } else {
+            Some(hints)
+        }
+    }
+}
+
+fn string_value(item: &Item) -> Option<String> {
+    match &item.bare_item {
+        BareItem::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the string items of a List, failing if any member is not a string.
+fn string_items(s: &str) -> Option<Vec<Item>> {
+    parse_list(s)
+        .ok()?
+        .into_iter()
+        .map(|member| match member {
+            Member::Item(
+                item @ Item {
+                    bare_item: BareItem::String(_),
+                    ..
+                },
+            ) => Some(item),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_brand_list(s: &str) -> Option<Vec<Brand>> {
+    string_items(s).map(|items| {
+        items
+            .into_iter()
+            .map(|item| Brand {
+                brand: string_value(&item).unwrap_or_default(),
+                version: item
+                    .params
+                    .iter()
+                    .find(|(k, _)| k == "v")
+                    .and_then(|(_, v)| match v {
+                        BareItem::String(v) => Some(v.clone()),
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
+            })
+            .collect()
+    })
+}
+
+fn parse_string_list(s: &str) -> Option<Vec<String>> {
+    string_items(s).map(|items| items.iter().filter_map(string_value).collect())
+}
+
+fn parse_string(s: &str) -> Option<String> {
+    string_value(&parse_item(s).ok()?)
+}
+
+fn parse_boolean(s: &str) -> Option<bool> {
+    match parse_item(s).ok()?.bare_item {
+        BareItem::Boolean(b) => Some(b),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_string() {
+        assert_eq!(parse_string(r#""Windows""#), Some("Windows".to_string()));
+        assert_eq!(parse_string(r#""""#), Some(String::new()));
+        assert_eq!(parse_string(r#""a\"b\\c""#), Some(r#"a"b\c"#.to_string()));
+        assert_eq!(parse_string(r#""unterminated"#), None);
+        assert_eq!(parse_string(r#""a" trailing"#), None);
+        assert_eq!(parse_string(r#""a";p=1"#), Some("a".to_string()));
+        assert_eq!(parse_string("token"), None);
+    }
+
+    #[test]
+    fn test_parse_boolean() {
+        assert_eq!(parse_boolean("?1"), Some(true));
+        assert_eq!(parse_boolean("?0"), Some(false));
+        assert_eq!(parse_boolean("1"), None);
+    }
+
+    #[test]
+    fn test_parse_brand_list() {
+        let brands = parse_brand_list(
+            r#""Chromium";v="124.0.6367.91", "Google Chrome";v="124.0.6367.91", "Not-A.Brand";v="99.0.0.0""#,
+        )
+        .unwrap();
+        assert_eq!(brands.len(), 3);
+        assert_eq!(
+            brands[0],
+            Brand {
+                brand: "Chromium".to_string(),
+                version: "124.0.6367.91".to_string(),
+            }
+        );
+        assert_eq!(brands[2].brand, "Not-A.Brand");
+    }
+
+    #[test]
+    fn test_parse_brand_list_escaped_brand() {
+        let brands = parse_brand_list(r#"" Not;A\\Brand";v="99""#).unwrap();
+        assert_eq!(brands[0].brand, r#" Not;A\Brand"#);
+        assert_eq!(brands[0].version, "99");
+    }
+
+    #[test]
+    fn test_parse_brand_list_invalid() {
+        assert_eq!(parse_brand_list(r#""Chromium";v="124","#), None);
+        assert_eq!(parse_brand_list(r#""Chromium" "Chrome""#), None);
+        assert_eq!(parse_brand_list("Chromium"), None);
+        assert_eq!(parse_brand_list(r#"("Chromium")"#), None);
+    }
+
+    #[test]
+    fn test_parse_string_list() {
+        assert_eq!(
+            parse_string_list(r#""Desktop", "XR""#),
+            Some(vec!["Desktop".to_string(), "XR".to_string()])
+        );
+        assert_eq!(parse_string_list(""), Some(vec![]));
+    }
+
+    #[test]
+    fn test_from_headers_none_without_hints() {
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", "curl/8.0".parse().unwrap());
+        assert_eq!(ClientHints::from_headers(&headers, |_| true), None);
+    }
+
+    #[test]
+    fn test_from_headers_high_entropy() {
+        let mut headers = HeaderMap::new();
+        headers.insert("sec-ch-ua-platform-version", r#""15.0.0""#.parse().unwrap());
+        headers.insert("sec-ch-ua-arch", r#""x86""#.parse().unwrap());
+        headers.insert("sec-ch-ua-bitness", r#""64""#.parse().unwrap());
+        headers.insert("sec-ch-ua-wow64", "?0".parse().unwrap());
+        headers.insert("sec-ch-ua-model", r#""""#.parse().unwrap());
+
+        let hints = ClientHints::from_headers(&headers, |_| true).unwrap();
+        assert_eq!(hints.platform_version.as_deref(), Some("15.0.0"));
+        assert_eq!(hints.arch.as_deref(), Some("x86"));
+        assert_eq!(hints.bitness.as_deref(), Some("64"));
+        assert_eq!(hints.wow64, Some(false));
+        assert_eq!(hints.model.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_hint_lists_are_consistent() {
+        for hint in HIGH_ENTROPY_HINTS {
+            assert!(HEADERS.contains(&hint.to_ascii_lowercase().as_str()));
+        }
+        for hint in CRITICAL_HINTS {
+            assert!(HIGH_ENTROPY_HINTS.contains(hint));
+        }
+        assert_eq!(HEADER_NAMES.len(), HEADERS.len());
+    }
+
+    #[test]
+    fn test_from_headers_respects_visibility() {
+        let mut headers = HeaderMap::new();
+        headers.insert("sec-ch-ua-platform", r#""Linux""#.parse().unwrap());
+        headers.insert("sec-ch-ua-model", r#""Pixel 8""#.parse().unwrap());
+
+        let hints = ClientHints::from_headers(&headers, |name| name != "sec-ch-ua-model").unwrap();
+        assert_eq!(hints.platform.as_deref(), Some("Linux"));
+        assert_eq!(hints.model, None);
+    }
+}
@@ -0,0 +1,260 @@
+//! Content codings for response bodies.
+//!
+//! The [`compress`] middleware encodes HTML and serialized responses in the coding
+//! negotiated from the request's `Accept-Encoding` header. Embedded static
+//! assets are compressed at build time instead; see [`Precompressed`].
+//!
+//! # Example
+//!
+//! ```
+//! use axum::http::{header, HeaderMap};
+//! use ip_info::compression::Encoding;
+//!
+//! let mut headers = HeaderMap::new();
+//! headers.insert(header::ACCEPT_ENCODING, "gzip, deflate, br, zstd".parse().unwrap());
+//! assert_eq!(Encoding::negotiate(&headers), Encoding::Brotli);
+//!
+//! // Without the header, responses are not encoded
+//! assert_eq!(Encoding::negotiate(&HeaderMap::new()), Encoding::Identity);
+//! ```
+
+use std::io::Write;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::content_negotiation::{negotiate_encoding, parse_accept_encoding};
+use crate::negotiated::Vary;
+
+/// Media types the [`compress`] middleware encodes.
+const COMPRESSIBLE: &[&str] = &[
+    "text/html",
+    "application/json",
+    "application/schema+json",
+    "application/javascript",
+    "application/yaml",
+    "application/toml",
+    "application/xml",
+];
+
+/// A content coding supported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    /// All encodings, in order of server preference.
+    pub const ALL: [Encoding; 4] = [
+        Encoding::Brotli,
+        Encoding::Zstd,
+        Encoding::Gzip,
+        Encoding::Identity,
+    ];
+
+    /// Returns the name of the coding, as used in `Content-Encoding`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+            Encoding::Identity => "identity",
+        }
+    }
+
+    /// Selects the encoding for a response to a request.
+    ///
+    /// Without an `Accept-Encoding` header, or if no coding is acceptable,
+    /// the response is not encoded.
+    pub fn negotiate(headers: &HeaderMap) -> Self {
+        let Some(accept_encoding) = headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return Encoding::Identity;
+        };
+
+        let codings = parse_accept_encoding(accept_encoding);
+        let available = Encoding::ALL.map(Encoding::as_str);
+        negotiate_encoding(&codings, &available)
+            .map(|i| Encoding::ALL[i])
+            .unwrap_or(Encoding::Identity)
+    }
+
+    /// Encodes a body.
+    pub fn encode(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Brotli => {
+                let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                writer.write_all(data).unwrap();
+                writer.into_inner()
+            }
+            Encoding::Zstd => zstd::encode_all(data, 3).unwrap(),
+            Encoding::Gzip => {
+                let mut writer =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                writer.write_all(data).unwrap();
+                writer.finish().unwrap()
+            }
+            Encoding::Identity => data.to_vec(),
+        }
+    }
+}
+
+/// A static asset with variants compressed at build time.
+#[derive(Debug, Clone, Copy)]
+pub struct Precompressed {
+    pub identity: &'static [u8],
+    pub brotli: &'static [u8],
+    pub zstd: &'static [u8],
+    pub gzip: &'static [u8],
+}
+
+impl Precompressed {
+    /// Returns the variant in the given encoding.
+    pub fn get(&self, encoding: Encoding) -> &'static [u8] {
+        match encoding {
+            Encoding::Brotli => self.brotli,
+            Encoding::Zstd => self.zstd,
+            Encoding::Gzip => self.gzip,
+            Encoding::Identity => self.identity,
+        }
+    }
+}
+
+/// Embeds a file from `src/` together with the variants `build.rs`
+/// compressed it to.
+macro_rules! precompressed {
+    ($asset:literal) => {
+        $crate::compression::Precompressed {
+            identity: include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/", $asset)),
+            brotli: include_bytes!(concat!(env!("OUT_DIR"), "/", $asset, ".br")),
+            zstd: include_bytes!(concat!(env!("OUT_DIR"), "/", $asset, ".zst")),
+            gzip: include_bytes!(concat!(env!("OUT_DIR"), "/", $asset, ".gz")),
+        }
+    };
+}
+pub(crate) use precompressed;
+
+fn is_compressible(response: &Response) -> bool {
+    if response.status() == StatusCode::NO_CONTENT
+        || response.status() == StatusCode::NOT_MODIFIED
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return false;
+    }
+
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|media_type| {
+            COMPRESSIBLE
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(media_type.trim()))
+        })
+}
+
+/// Middleware encoding HTML and JSON responses in the negotiated coding.
+///
+/// Responses that already have a `Content-Encoding` are left alone. Records
+/// `Accept-Encoding` in the request's [`Vary`] set.
+pub async fn compress(request: Request, next: Next) -> Response {
+    let vary = request
+        .extensions()
+        .get::<Vary>()
+        .cloned()
+        .unwrap_or_default();
+    let encoding = Encoding::negotiate(request.headers());
+
+    let response = next.run(request).await;
+    if !is_compressible(&response) {
+        return response;
+    }
+
+    vary.add(header::ACCEPT_ENCODING);
+    if encoding == Encoding::Identity {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+    Response::from_parts(parts, Body::from(encoding.encode(&body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn accept_encoding(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(
+            Encoding::negotiate(&accept_encoding("gzip, zstd")),
+            Encoding::Zstd
+        );
+        assert_eq!(
+            Encoding::negotiate(&accept_encoding("br;q=0.5, gzip")),
+            Encoding::Gzip
+        );
+        assert_eq!(
+            Encoding::negotiate(&accept_encoding("deflate")),
+            Encoding::Identity
+        );
+        assert_eq!(
+            Encoding::negotiate(&accept_encoding("*;q=0")),
+            Encoding::Identity
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let data = b"{\"ip\":\"127.0.0.1\"}".repeat(10);
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(Encoding::Gzip.encode(&data).as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        let decoded = zstd::decode_all(Encoding::Zstd.encode(&data).as_slice()).unwrap();
+        assert_eq!(decoded, data);
+
+        let mut decoded = Vec::new();
+        brotli::Decompressor::new(Encoding::Brotli.encode(&data).as_slice(), 4096)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_precompressed_css() {
+        let css = precompressed!("main.css");
+        let decoded = zstd::decode_all(css.get(Encoding::Zstd)).unwrap();
+        assert_eq!(decoded, css.get(Encoding::Identity));
+    }
+}
@@ -0,0 +1,114 @@
+//! Runtime configuration read from environment variables at startup.
+//!
+//! | Variable                 | Meaning                                               |
+//! |--------------------------|-------------------------------------------------------|
+//! | `HEADERS_ALLOW`          | Comma-separated header patterns to show (default all) |
+//! | `HEADERS_DENY`           | Header patterns never shown (default proxy headers)   |
+//! | `HEADERS_REDACT`         | Header patterns whose values are redacted             |
+//! | `HEADERS_REDACTION_MODE` | `hide`, `mask` (default) or `hash`                    |
+//! | `UA_REGEXES`             | Path to a uap-core `regexes.yaml` (default built-in)  |
+//! | `NEGOTIATION_LENIENT`    | Serve the default format instead of `406` (`false`)   |
+//! | `CORS_ALLOW_ORIGINS`     | Comma-separated origins allowed by CORS or `*` (none) |
+//! | `CORS_ALLOW_CREDENTIALS` | Allow credentialed CORS requests (`false`)            |
+//! | `JSONP`                  | Answer `?callback=` with JSONP (`false`)              |
+//! | `EVENTS_INTERVAL`        | Seconds between `/events` updates (default 15)        |
+//!
+//! Setting a list variable to an empty string clears the corresponding default.
+//! Boolean variables accept `1`/`true`/`yes` and `0`/`false`/`no`.
+
+use std::{env, time::Duration};
+
+use anyhow::{bail, Context, Result};
+
+use crate::cors::CorsPolicy;
+use crate::header_policy::{parse_patterns, HeaderPolicy};
+use crate::user_agent::UserAgentParser;
+
+/// Configuration shared by all request handlers.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Which request headers are echoed back and how
+    pub header_policy: HeaderPolicy,
+    /// Rules used to parse the `User-Agent` header
+    pub user_agent_parser: UserAgentParser,
+    /// Whether to fall back to the default representation instead of
+    /// responding `406 Not Acceptable`
+    pub lenient_negotiation: bool,
+    /// Which origins may read responses cross-origin
+    pub cors: CorsPolicy,
+    /// Whether to wrap JSON in the `callback` query parameter
+    pub jsonp: bool,
+    /// Time between the updates of an `/events` stream
+    pub events_interval: Duration,
+}
+
+impl Config {
+    /// Builds the configuration from the process environment.
+    pub fn from_env() -> Result<Self> {
+        let mut header_policy = HeaderPolicy::default();
+        if let Ok(allow) = env::var("HEADERS_ALLOW") {
+            header_policy.allow = parse_patterns(&allow);
+        }
+        if let Ok(deny) = env::var("HEADERS_DENY") {
+            header_policy.deny = parse_patterns(&deny);
+        }
+        if let Ok(redact) = env::var("HEADERS_REDACT") {
+            header_policy.redact = parse_patterns(&redact);
+        }
+        if let Ok(mode) = env::var("HEADERS_REDACTION_MODE") {
+            header_policy.mode = mode.parse().context("HEADERS_REDACTION_MODE")?;
+        }
+
+        let user_agent_parser = match env::var("UA_REGEXES") {
+            Ok(path) => {
+                UserAgentParser::from_path(&path).with_context(|| format!("UA_REGEXES={path}"))?
+            }
+            Err(_) => UserAgentParser::builtin(),
+        };
+
+        // Off by default: responses echo request headers, possibly including
+        // identity headers added by a proxy
+        let cors = env::var("CORS_ALLOW_ORIGINS")
+            .as_deref()
+            .unwrap_or("")
+            .parse::<CorsPolicy>()
+            .context("CORS_ALLOW_ORIGINS")?
+            .with_credentials(env_flag("CORS_ALLOW_CREDENTIALS")?.unwrap_or(false))
+            .context("CORS_ALLOW_CREDENTIALS")?;
+
+        Ok(Config {
+            header_policy,
+            user_agent_parser,
+            lenient_negotiation: env_flag("NEGOTIATION_LENIENT")?.unwrap_or(false),
+            cors,
+            // Off by default: unlike CORS, script tags send cookies
+            jsonp: env_flag("JSONP")?.unwrap_or(false),
+            events_interval: env_seconds("EVENTS_INTERVAL")?.unwrap_or(Duration::from_secs(15)),
+        })
+    }
+}
+
+/// Reads a positive number of seconds, returning `None` if it is unset.
+fn env_seconds(name: &str) -> Result<Option<Duration>> {
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+
+    match value.trim().parse::<u64>() {
+        Ok(seconds) if seconds > 0 => Ok(Some(Duration::from_secs(seconds))),
+        _ => bail!("{name}: invalid number of seconds {value:?}"),
+    }
+}
+
+/// Reads a boolean environment variable, returning `None` if it is unset.
+fn env_flag(name: &str) -> Result<Option<bool>> {
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(Some(true)),
+        "0" | "false" | "no" | "" => Ok(Some(false)),
+        _ => bail!("{name}: invalid boolean {value:?}"),
+    }
+}
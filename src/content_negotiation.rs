@@ -10,8 +10,17 @@
 //!
 //! - [`MediaType`] - Represents a media type with optional parameters
 //! - [`Directive`] - Represents an Accept header directive with quality value
+//! - [`QValue`] - A validated quality value in fixed-point representation
 //! - [`parse_accept_directive()`] - Parses individual Accept header directives
 //! - [`parse_accept()`] - Parses complete Accept headers and sorts by preference
+//! - [`negotiate()`] - Selects the best of the server's offered media types
+//! - [`Negotiator`] - Declares an endpoint's representations with server-side quality
+//! - [`LanguageRange`] / [`parse_accept_language()`] - Parses Accept-Language headers
+//! - [`negotiate_language()`] - Selects the best of the server's available languages
+//! - [`Coding`] / [`parse_accept_encoding()`] - Parses Accept-Encoding headers
+//! - [`negotiate_encoding()`] - Selects the best of the server's content codings
+//! - [`CharsetRange`] / [`parse_accept_charset()`] - Parses Accept-Charset headers
+//! - [`rank_charsets()`] - Orders the server's charsets by client preference
 //!
 //! # Example
 //!
@@ -46,7 +55,13 @@
 //!
 //! This implementation follows the HTTP specifications:
 //! - [RFC 7231](https://tools.ietf.org/html/rfc7231) - HTTP/1.1 Semantics and Content
+//! - [RFC 9110 Section 12.5.1](https://www.rfc-editor.org/rfc/rfc9110#section-12.5.1) - Accept
+//! - [RFC 9110 Section 12.5.2](https://www.rfc-editor.org/rfc/rfc9110#section-12.5.2) - Accept-Charset
+//! - [RFC 9110 Section 12.5.3](https://www.rfc-editor.org/rfc/rfc9110#section-12.5.3) - Accept-Encoding
 //! - [RFC 6838](https://tools.ietf.org/html/rfc6838) - Media Type Specifications
+//! - [RFC 4647](https://www.rfc-editor.org/rfc/rfc4647) - Matching of Language Tags
+
+use std::{fmt, str::FromStr};
 
 use thiserror::Error;
 
@@ -56,15 +71,47 @@ pub enum ParseError {
     /// The media type string is missing a required '/' separator
     #[error("media type must contain a '/' separator")]
     MissingSlash,
-    /// The quality value (q-value) is not a valid float between 0.0 and 1.0
+    /// The type before the '/' separator is empty
+    #[error("media type has an empty type")]
+    EmptyType,
+    /// The subtype after the '/' separator is empty
+    #[error("media type has an empty subtype")]
+    EmptySubtype,
+    /// A character is not allowed at its position by the grammar
+    #[error("unexpected character {0:?} at position {1}")]
+    UnexpectedCharacter(char, usize),
+    /// A parameter has a value but no name
+    #[error("parameter has an empty name")]
+    EmptyParameterName,
+    /// A parameter has a name but no '=' and value
+    #[error("parameter {0:?} has no value")]
+    MissingParameterValue(String),
+    /// A parameter has an '=' but nothing after it
+    #[error("parameter {0:?} has an empty value")]
+    EmptyParameterValue(String),
+    /// A quoted-string is missing its closing '"'
+    #[error("unterminated quoted string")]
+    UnterminatedQuotedString,
+    /// The quality value (q-value) is not between 0 and 1 with at most three decimals
     #[error("invalid quality value: {0}")]
     InvalidQualityValue(String),
+    /// The language range is not a valid basic or extended language range
+    #[error("invalid language range: {0}")]
+    InvalidLanguageRange(String),
+    /// The content coding is not a valid token
+    #[error("invalid content coding: {0}")]
+    InvalidCoding(String),
+    /// The charset is not a valid token
+    #[error("invalid charset: {0}")]
+    InvalidCharset(String),
 }
 
 /// Represents a media type (MIME type) with optional parameters.
 ///
 /// A media type consists of a main type and sub-type separated by a slash,
 /// followed by optional parameters in the form `key=value` separated by semicolons.
+/// Type, subtype and parameter names are case-insensitive and stored lowercased;
+/// parameter values are stored without quotes.
 ///
 /// This follows the format defined in [RFC 6838](https://tools.ietf.org/html/rfc6838)
 /// and [RFC 7231 Section 3.1.1.1](https://tools.ietf.org/html/rfc7231#section-3.1.1.1).
@@ -90,11 +137,389 @@ pub struct MediaType {
     pub parameters: Vec<(String, String)>,
 }
 
+/// Characters allowed in a token besides ASCII letters and digits, as defined
+/// in [RFC 9110 Section 5.6.2](https://www.rfc-editor.org/rfc/rfc9110#section-5.6.2).
+const TCHAR_SYMBOLS: &str = "!#$%&'*+-.^_`|~";
+
+fn is_tchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || TCHAR_SYMBOLS.contains(c)
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_tchar)
+}
+
+/// Checks for characters allowed in a quoted-string, including `obs-text`.
+fn is_qdtext(c: char) -> bool {
+    c == '\t' || c == ' ' || (c >= '!' && c != '\x7f') && c != '"' && c != '\\'
+}
+
+/// A cursor over a header value for the RFC 9110 grammar.
+struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Self {
+        Tokenizer { input, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Returns the error for the character at the current position.
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(c) => ParseError::UnexpectedCharacter(c, self.pos),
+            None => ParseError::UnterminatedQuotedString,
+        }
+    }
+
+    /// Consumes a possibly empty token.
+    fn token(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(is_tchar) {
+            self.pos += 1;
+        }
+        &self.input[start..self.pos]
+    }
+
+    /// Consumes optional whitespace (`OWS`).
+    fn skip_ows(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes a quoted-string starting at the current '"', unescaping
+    /// quoted-pairs.
+    fn quoted_string(&mut self) -> Result<String, ParseError> {
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::UnterminatedQuotedString),
+                Some('"') => {
+                    self.bump();
+                    return Ok(value);
+                }
+                Some('\\') => {
+                    self.bump();
+                    match self.peek() {
+                        Some(c) if c == '"' || c == '\\' || is_qdtext(c) => {
+                            value.push(c);
+                            self.bump();
+                        }
+                        _ => return Err(self.unexpected()),
+                    }
+                }
+                Some(c) if is_qdtext(c) => {
+                    value.push(c);
+                    self.bump();
+                }
+                Some(_) => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    /// Consumes `type "/" subtype`, returning them lowercased.
+    fn type_and_subtype(&mut self) -> Result<(String, String), ParseError> {
+        let main_type = self.token();
+        match self.peek() {
+            Some('/') if main_type.is_empty() => return Err(ParseError::EmptyType),
+            Some('/') => {
+                self.bump();
+            }
+            None if main_type.is_empty() => return Err(ParseError::EmptyType),
+            None => return Err(ParseError::MissingSlash),
+            Some(_) => return Err(self.unexpected()),
+        }
+
+        let sub_type = self.token();
+        if sub_type.is_empty() {
+            return match self.peek() {
+                None | Some(';') => Err(ParseError::EmptySubtype),
+                Some(_) => Err(self.unexpected()),
+            };
+        }
+
+        Ok((
+            main_type.to_ascii_lowercase(),
+            sub_type.to_ascii_lowercase(),
+        ))
+    }
+
+    /// Consumes `OWS ";" OWS` and the name of the next parameter, skipping
+    /// empty parameters. Returns `None` at the end of the input.
+    fn parameter_name(&mut self) -> Result<Option<String>, ParseError> {
+        loop {
+            self.skip_ows();
+            match self.peek() {
+                None => return Ok(None),
+                Some(';') => {
+                    self.bump();
+                }
+                Some(_) => return Err(self.unexpected()),
+            }
+            self.skip_ows();
+            if matches!(self.peek(), None | Some(';')) {
+                continue;
+            }
+
+            let name = self.token();
+            if name.is_empty() {
+                return match self.peek() {
+                    Some('=') => Err(ParseError::EmptyParameterName),
+                    _ => Err(self.unexpected()),
+                };
+            }
+            return Ok(Some(name.to_ascii_lowercase()));
+        }
+    }
+
+    /// Consumes `"=" ( token / quoted-string )` after a parameter name.
+    ///
+    /// If `optional` is set, a parameter without `=` has the value `None`;
+    /// otherwise it is an error.
+    fn parameter_value(
+        &mut self,
+        name: &str,
+        optional: bool,
+    ) -> Result<Option<String>, ParseError> {
+        if self.peek() != Some('=') {
+            let error = self.unexpected();
+            self.skip_ows();
+            return match self.peek() {
+                None | Some(';') if optional => Ok(None),
+                None | Some(';') => Err(ParseError::MissingParameterValue(name.to_string())),
+                _ => Err(error),
+            };
+        }
+        self.bump();
+
+        if self.peek() == Some('"') {
+            return self.quoted_string().map(Some);
+        }
+
+        let value = self.token();
+        if value.is_empty() {
+            return match self.peek() {
+                None | Some(';' | ' ' | '\t') => {
+                    Err(ParseError::EmptyParameterValue(name.to_string()))
+                }
+                Some(_) => Err(self.unexpected()),
+            };
+        }
+        Ok(Some(value.to_string()))
+    }
+
+    /// Consumes `"=" qvalue` after a `q` parameter name.
+    fn qvalue(&mut self) -> Result<QValue, ParseError> {
+        // The weight is a bare qvalue, never a quoted-string
+        if self.peek() == Some('=') {
+            self.bump();
+        }
+        self.token().parse()
+    }
+
+    /// Consumes the parameters after the element of an Accept-Language,
+    /// Accept-Encoding or Accept-Charset list, returning its weight.
+    ///
+    /// These headers only define the `q` parameter; others are skipped, but
+    /// must be well-formed as in a media type.
+    fn weight(&mut self) -> Result<QValue, ParseError> {
+        let mut q = QValue::ONE;
+        while let Some(name) = self.parameter_name()? {
+            if name == "q" {
+                q = self.qvalue()?;
+            } else {
+                self.parameter_value(&name, false)?;
+            }
+        }
+        Ok(q)
+    }
+
+    /// Consumes a media type: `type "/" subtype *( OWS ";" OWS [ parameter ] )`.
+    fn media_type(&mut self) -> Result<MediaType, ParseError> {
+        let (main_type, sub_type) = self.type_and_subtype()?;
+
+        let mut parameters = Vec::new();
+        while let Some(name) = self.parameter_name()? {
+            let value = self.parameter_value(&name, false)?.unwrap_or_default();
+            parameters.push((name, value));
+        }
+
+        Ok(MediaType {
+            main_type,
+            sub_type,
+            parameters,
+        })
+    }
+}
+
+/// Splits a comma-separated header value into its trimmed, non-empty
+/// elements, ignoring commas inside quoted-strings.
+fn split_list(header_value: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in header_value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                elements.push(&header_value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(&header_value[start..]);
+
+    elements
+        .into_iter()
+        .map(|e| e.trim_matches([' ', '\t']))
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+/// Parses an element of an Accept-Language, Accept-Encoding or Accept-Charset
+/// list: `token [ weight ]`.
+///
+/// The token may be empty, leaving its validation to the caller.
+fn weighted_token(element: &str) -> Result<(&str, QValue), ParseError> {
+    let mut tokenizer = Tokenizer::new(element);
+    let token = tokenizer.token();
+    let q = tokenizer.weight()?;
+    Ok((token, q))
+}
+
+/// A quality value ("qvalue"), as defined in
+/// [RFC 9110 Section 12.4.2](https://www.rfc-editor.org/rfc/rfc9110#section-12.4.2).
+///
+/// Quality values range from 0 to 1 with at most three decimal places, so
+/// they are stored exactly as thousandths. Comparing with an `f32` compares
+/// the nearest `f32` value.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::{ParseError, QValue};
+///
+/// let q: QValue = "0.25".parse().unwrap();
+/// assert_eq!(q.thousandths(), 250);
+/// assert_eq!(q.to_string(), "0.25");
+/// assert_eq!(q, 0.25);
+///
+/// assert!("1.001".parse::<QValue>().is_err());
+/// assert!("0.1234".parse::<QValue>().is_err());
+/// assert_eq!(
+///     "NaN".parse::<QValue>(),
+///     Err(ParseError::InvalidQualityValue("NaN".to_string()))
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QValue(u16);
+
+impl QValue {
+    /// Not acceptable
+    pub const ZERO: QValue = QValue(0);
+    /// The default quality
+    pub const ONE: QValue = QValue(1000);
+
+    /// Creates a quality value from thousandths, or `None` if above 1000.
+    pub fn from_thousandths(thousandths: u16) -> Option<Self> {
+        (thousandths <= 1000).then_some(QValue(thousandths))
+    }
+
+    /// Creates a quality value rounded to three decimal places, or `None` if
+    /// `value` is not between 0.0 and 1.0.
+    pub fn new(value: f32) -> Option<Self> {
+        (0.0..=1.0)
+            .contains(&value)
+            .then(|| QValue((value * 1000.0).round() as u16))
+    }
+
+    /// Returns the quality value in thousandths.
+    pub fn thousandths(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the quality value as a float.
+    pub fn as_f32(self) -> f32 {
+        self.0 as f32 / 1000.0
+    }
+
+    /// Checks if the quality value means "not acceptable".
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl Default for QValue {
+    fn default() -> Self {
+        QValue::ONE
+    }
+}
+
+impl PartialEq<f32> for QValue {
+    fn eq(&self, other: &f32) -> bool {
+        self.as_f32() == *other
+    }
+}
+
+impl FromStr for QValue {
+    type Err = ParseError;
+
+    /// Parses `( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidQualityValue(s.to_string());
+
+        let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let fraction: u16 = format!("{fraction:0<3}").parse().map_err(|_| invalid())?;
+
+        match integer {
+            "0" => Ok(QValue(fraction)),
+            "1" if fraction == 0 => Ok(QValue::ONE),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for QValue {
+    /// Formats the quality value without trailing zeros.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => f.write_str("0"),
+            1000 => f.write_str("1"),
+            n => {
+                let fraction = format!("{n:03}");
+                write!(f, "0.{}", fraction.trim_end_matches('0'))
+            }
+        }
+    }
+}
+
 /// Represents an Accept header directive with a media type and quality value.
 ///
 /// This is used to parse individual parts of an HTTP Accept header, where each
 /// directive specifies a media type and an optional quality value (q-value)
-/// indicating the client's preference for that media type.
+/// indicating the client's preference for that media type. Parameters after
+/// the q-value are accept extensions rather than media type parameters.
 ///
 /// # Examples
 ///
@@ -110,8 +535,10 @@ pub struct MediaType {
 pub struct Directive {
     /// The media type for this directive
     pub media_type: MediaType,
-    /// Quality value (0.0 to 1.0), defaults to 1.0 if not specified
-    pub q: f32,
+    /// Quality value (0 to 1), defaults to 1 if not specified
+    pub q: QValue,
+    /// Accept extensions following the q-value; their values are optional
+    pub extensions: Vec<(String, Option<String>)>,
 }
 
 impl MediaType {
@@ -148,6 +575,105 @@ impl MediaType {
 
         main_matches && sub_matches
     }
+
+    /// Returns how specific this media type is when used as a media range.
+    ///
+    /// `*/*` is least specific, followed by `type/*`, `type/subtype`, and
+    /// `type/subtype` with parameters, where each parameter adds specificity.
+    /// Like in [`includes`](Self::includes), `charset` is not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ip_info::content_negotiation::MediaType;
+    /// use std::convert::TryFrom;
+    ///
+    /// let any = MediaType::try_from("*/*").unwrap();
+    /// let text = MediaType::try_from("text/*").unwrap();
+    /// let plain = MediaType::try_from("text/plain").unwrap();
+    /// let flowed = MediaType::try_from("text/plain;format=flowed").unwrap();
+    ///
+    /// assert!(any.specificity() < text.specificity());
+    /// assert!(text.specificity() < plain.specificity());
+    /// assert!(plain.specificity() < flowed.specificity());
+    ///
+    /// let ascii = MediaType::try_from("text/plain;charset=us-ascii").unwrap();
+    /// assert_eq!(ascii.specificity(), plain.specificity());
+    /// ```
+    pub fn specificity(&self) -> usize {
+        if self.main_type == "*" {
+            0
+        } else if self.sub_type == "*" {
+            1
+        } else {
+            let parameters = self
+                .parameters
+                .iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("charset"));
+            2 + parameters.count()
+        }
+    }
+
+    /// Checks if this media range includes a (non-wildcard) media type.
+    ///
+    /// In addition to [`matches`](Self::matches), every parameter of the range
+    /// must be present on the media type with the same value. Parameter names
+    /// are compared case-insensitively. The `charset` parameter is ignored, as
+    /// it is negotiated separately (see [`charset_ranges()`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ip_info::content_negotiation::MediaType;
+    /// use std::convert::TryFrom;
+    ///
+    /// let range = MediaType::try_from("text/plain;format=flowed").unwrap();
+    /// let flowed = MediaType::try_from("text/plain;format=flowed;charset=utf-8").unwrap();
+    /// let plain = MediaType::try_from("text/plain").unwrap();
+    ///
+    /// assert!(range.includes(&flowed));
+    /// assert!(!range.includes(&plain));
+    /// assert!(plain.includes(&flowed));
+    /// ```
+    pub fn includes(&self, media_type: &MediaType) -> bool {
+        self.matches(media_type)
+            && self.parameters.iter().all(|(name, value)| {
+                name.eq_ignore_ascii_case("charset")
+                    || media_type
+                        .parameters
+                        .iter()
+                        .any(|(n, v)| n.eq_ignore_ascii_case(name) && v == value)
+            })
+    }
+}
+
+impl fmt::Display for MediaType {
+    /// Formats the media type in canonical form.
+    ///
+    /// Names are lowercase, parameters are separated by `;` without
+    /// whitespace, and values are quoted only if they are not tokens.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ip_info::content_negotiation::MediaType;
+    /// use std::convert::TryFrom;
+    ///
+    /// let media_type = MediaType::try_from(r#"Text/HTML ; Charset=utf-8; title="a \"b\"""#).unwrap();
+    /// assert_eq!(media_type.to_string(), r#"text/html;charset=utf-8;title="a \"b\"""#);
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.main_type, self.sub_type)?;
+        for (name, value) in &self.parameters {
+            if is_token(value) {
+                write!(f, ";{name}={value}")?;
+            } else {
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, ";{name}=\"{escaped}\"")?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl TryFrom<&str> for MediaType {
@@ -155,48 +681,45 @@ impl TryFrom<&str> for MediaType {
 
     /// Parses a media type string into a MediaType struct.
     ///
-    /// The string should be in the format: `main_type/sub_type[;param1=value1;param2=value2;...]`
+    /// The string must follow the `media-type` grammar of
+    /// [RFC 9110 Section 8.3.1](https://www.rfc-editor.org/rfc/rfc9110#section-8.3.1):
+    /// `type/subtype` followed by `;name=value` parameters, where type,
+    /// subtype and names are tokens and values are tokens or quoted-strings.
+    /// Whitespace is only allowed around the `;` separators.
     ///
     /// # Errors
     ///
-    /// Returns a `ParseError::MissingSlash` if the string doesn't contain a '/' separator.
+    /// Returns a `ParseError` describing the first violation of the grammar.
     ///
     /// # Examples
     ///
     /// ```
-    /// use ip_info::content_negotiation::MediaType;
+    /// use ip_info::content_negotiation::{MediaType, ParseError};
     /// use std::convert::TryFrom;
     ///
     /// let simple = MediaType::try_from("text/html").unwrap();
     /// let with_params = MediaType::try_from("text/html;charset=utf-8;boundary=something").unwrap();
     /// let wildcard = MediaType::try_from("*/*").unwrap();
     ///
-    /// // Error case
-    /// assert!(MediaType::try_from("invalid").is_err());
+    /// // Quoted values may contain separators
+    /// let quoted = MediaType::try_from(r#"text/plain;title="a;b,c""#).unwrap();
+    /// assert_eq!(quoted.parameters[0].1, "a;b,c");
+    ///
+    /// // Error cases
+    /// assert_eq!(MediaType::try_from("invalid"), Err(ParseError::MissingSlash));
+    /// assert_eq!(MediaType::try_from("text /html"), Err(ParseError::UnexpectedCharacter(' ', 4)));
+    /// assert_eq!(MediaType::try_from("/html"), Err(ParseError::EmptyType));
     /// ```
     fn try_from(s: &str) -> Result<Self, ParseError> {
-        let (media_main, rest) = s.split_once('/').ok_or(ParseError::MissingSlash)?;
-        let parts: Vec<&str> = rest.split(';').collect();
-        let sub_type = parts[0];
-
-        let parameters = parts[1..]
-            .iter()
-            .filter_map(|param| param.split_once('='))
-            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
-            .collect();
-
-        Ok(MediaType {
-            main_type: media_main.to_string(),
-            sub_type: sub_type.to_string(),
-            parameters,
-        })
+        Tokenizer::new(s).media_type()
     }
 }
 
 /// Parses a single Accept header directive into a Directive struct.
 ///
-/// An Accept directive has the format: `media_type[;param=value;...][;q=quality_value]`
+/// An Accept directive has the format: `media_type[;param=value;...][;q=quality_value[;ext[=value];...]]`
 /// The quality value (q) is separated from other parameters and defaults to 1.0 if not specified.
+/// Parameters after it are collected as [`Directive::extensions`].
 ///
 /// # Arguments
 ///
@@ -209,8 +732,8 @@ impl TryFrom<&str> for MediaType {
 /// # Errors
 ///
 /// Returns a `ParseError` if:
-/// - The directive doesn't contain a '/' (invalid media type)
-/// - The q-value cannot be parsed as a float
+/// - The media type does not follow the RFC 9110 grammar (see [`MediaType::try_from`])
+/// - The q-value is not between 0 and 1 with at most three decimals
 ///
 /// # Examples
 ///
@@ -230,39 +753,46 @@ impl TryFrom<&str> for MediaType {
 /// // Error cases
 /// assert!(parse_accept_directive("invalid").is_err());
 /// assert!(parse_accept_directive("text/html;q=invalid").is_err());
+/// assert!(parse_accept_directive("text/html;q=7").is_err());
+///
+/// // Parameters after q are accept extensions
+/// let with_ext = parse_accept_directive("text/html;level=1;q=0.5;ext;foo=\"bar\"").unwrap();
+/// assert_eq!(with_ext.media_type.parameters.len(), 1);
+/// assert_eq!(
+///     with_ext.extensions,
+///     vec![("ext".to_string(), None), ("foo".to_string(), Some("bar".to_string()))]
+/// );
 /// ```
 pub fn parse_accept_directive(directive_str: &str) -> Result<Directive, ParseError> {
-    let (media_main, rest) = directive_str
-        .split_once('/')
-        .ok_or(ParseError::MissingSlash)?;
-
-    let parts: Vec<&str> = rest.split(';').collect();
-    let sub_type = parts[0];
+    let mut tokenizer = Tokenizer::new(directive_str);
+    let (main_type, sub_type) = tokenizer.type_and_subtype()?;
 
-    let mut q = 1.0;
     let mut parameters = Vec::new();
-
-    for param_str in &parts[1..] {
-        if let Some((key, value)) = param_str.split_once('=') {
-            let key = key.trim();
-            let value = value.trim();
-            if key == "q" {
-                q = value
-                    .parse::<f32>()
-                    .map_err(|_| ParseError::InvalidQualityValue(value.to_string()))?;
-            } else {
-                parameters.push((key.to_string(), value.to_string()));
-            }
+    let mut q = None;
+    let mut extensions = Vec::new();
+    while let Some(name) = tokenizer.parameter_name()? {
+        if q.is_some() {
+            let value = tokenizer.parameter_value(&name, true)?;
+            extensions.push((name, value));
+        } else if name == "q" {
+            q = Some(tokenizer.qvalue()?);
+        } else {
+            let value = tokenizer.parameter_value(&name, false)?.unwrap_or_default();
+            parameters.push((name, value));
         }
     }
 
     let media_type = MediaType {
-        main_type: media_main.to_string(),
-        sub_type: sub_type.to_string(),
+        main_type,
+        sub_type,
         parameters,
     };
 
-    Ok(Directive { media_type, q })
+    Ok(Directive {
+        media_type,
+        q: q.unwrap_or_default(),
+        extensions,
+    })
 }
 
 /// Parses a complete HTTP Accept header value into a sorted list of directives.
@@ -295,55 +825,778 @@ pub fn parse_accept_directive(directive_str: &str) -> Result<Directive, ParseErr
 /// assert_eq!(directives[1].media_type.sub_type, "html"); // q=0.9
 /// assert_eq!(directives[2].media_type.sub_type, "plain"); // q=0.8
 /// ```
-pub fn parse_accept(header_value: &str) -> Vec<Directive> {
-    let mut r = header_value
-        .split(',')
-        .filter_map(|s| parse_accept_directive(s.trim()).ok())
-        .collect::<Vec<Directive>>();
-    r.sort_by(|a, b| b.q.partial_cmp(&a.q).unwrap());
+pub fn parse_accept(header_value: &str) -> Vec<Directive> {
+    let mut r = split_list(header_value)
+        .into_iter()
+        .filter_map(|s| parse_accept_directive(s).ok())
+        .collect::<Vec<Directive>>();
+    r.sort_by_key(|d| std::cmp::Reverse(d.q));
+    r
+}
+
+/// Parses a complete HTTP Accept header value into a sorted list of directives.
+///
+/// This is the strict version that returns an error if any directive fails to parse.
+///
+/// # Arguments
+///
+/// * `header_value` - The complete Accept header value as a string slice
+///
+/// # Returns
+///
+/// A `Result<Vec<Directive>, ParseError>` containing all parsed directives sorted by
+/// quality value in descending order, or an error if any directive is invalid.
+///
+/// # Errors
+///
+/// Returns the first `ParseError` encountered when parsing directives.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::try_parse_accept;
+///
+/// let accept = "text/html;q=0.9,application/json;q=1.0,text/plain;q=0.8";
+/// let directives = try_parse_accept(accept).unwrap();
+///
+/// // Results are sorted by quality value (highest first)
+/// assert_eq!(directives[0].media_type.sub_type, "json"); // q=1.0
+/// assert_eq!(directives[1].media_type.sub_type, "html"); // q=0.9
+/// assert_eq!(directives[2].media_type.sub_type, "plain"); // q=0.8
+///
+/// // Error case
+/// assert!(try_parse_accept("text/html,invalid,application/json").is_err());
+/// ```
+pub fn try_parse_accept(header_value: &str) -> Result<Vec<Directive>, ParseError> {
+    let mut r = split_list(header_value)
+        .into_iter()
+        .map(parse_accept_directive)
+        .collect::<Result<Vec<Directive>, ParseError>>()?;
+    r.sort_by_key(|d| std::cmp::Reverse(d.q));
+    Ok(r)
+}
+
+/// Returns the quality the client assigns to a media type.
+///
+/// The quality comes from the most specific directive whose media range
+/// includes the media type; among equally specific directives the first one
+/// wins. Returns the quality and the index of that directive, or `None` if no
+/// directive applies.
+fn quality_of(directives: &[Directive], media_type: &MediaType) -> Option<(QValue, usize)> {
+    directives
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.media_type.includes(media_type))
+        .min_by_key(|(i, d)| (std::cmp::Reverse(d.media_type.specificity()), *i))
+        .map(|(i, d)| (d.q, i))
+}
+
+/// Selects the offered media type the client prefers, following RFC 9110.
+///
+/// Each offer takes its quality from the most specific matching media range
+/// in `directives`, so `text/html;q=0.5, */*` ranks HTML below everything
+/// else. Offers with a quality of 0 are not acceptable. Ties are broken by
+/// the order of the directives in the header (as returned by
+/// [`parse_accept()`], whose sort is stable), then by the order of `offers`.
+///
+/// Returns the index into `offers` of the selected media type, or `None` if
+/// none is acceptable.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::{negotiate, parse_accept, MediaType};
+/// use std::convert::TryFrom;
+///
+/// let offers = [
+///     MediaType::try_from("text/plain").unwrap(),
+///     MediaType::try_from("text/html").unwrap(),
+/// ];
+///
+/// // The specific text/html range overrides the wildcard
+/// let directives = parse_accept("*/*;q=0.9, text/html");
+/// assert_eq!(negotiate(&directives, &offers), Some(1));
+///
+/// // Equal quality: the range listed first in the header wins
+/// let directives = parse_accept("text/html;q=0.9, text/plain;q=0.9");
+/// assert_eq!(negotiate(&directives, &offers), Some(1));
+///
+/// // q=0 means "not acceptable"
+/// let directives = parse_accept("text/*;q=0");
+/// assert_eq!(negotiate(&directives, &offers), None);
+/// ```
+pub fn negotiate(directives: &[Directive], offers: &[MediaType]) -> Option<usize> {
+    select(directives, offers.iter().map(|offer| (offer, QValue::ONE)))
+}
+
+/// Selects the offer with the highest combined quality `q * qs`.
+///
+/// Returns the index of the selected offer; see [`negotiate()`] for how ties
+/// are broken.
+fn select<'a>(
+    directives: &[Directive],
+    offers: impl Iterator<Item = (&'a MediaType, QValue)>,
+) -> Option<usize> {
+    offers
+        .enumerate()
+        .filter_map(|(i, (offer, qs))| {
+            let (q, directive_index) = quality_of(directives, offer)?;
+            let score = u32::from(q.thousandths()) * u32::from(qs.thousandths());
+            (score > 0).then_some((i, score, directive_index))
+        })
+        .min_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)).then(a.0.cmp(&b.0)))
+        .map(|(i, _, _)| i)
+}
+
+/// A representation offered by the server, with its server-side quality.
+#[derive(Debug)]
+pub struct Offer<T> {
+    /// The media type of the representation
+    pub media_type: MediaType,
+    /// Server-side quality (0 to 1), like Apache's `qs`
+    pub qs: QValue,
+    /// The value returned when this offer is selected
+    pub value: T,
+}
+
+/// Selects among the representations an endpoint can produce.
+///
+/// Each offer has a server-side quality `qs` expressing how good the server
+/// considers that representation. The selected offer maximizes the product of
+/// the client's quality (from the most specific matching media range) and
+/// `qs`; ties are broken as in [`negotiate()`].
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::{parse_accept, Negotiator};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum Format {
+///     Plain,
+///     Html,
+///     Json,
+/// }
+///
+/// let negotiator = Negotiator::new()
+///     .offer("text/plain", Format::Plain)
+///     .offer("text/html", Format::Html)
+///     .offer_with_qs("application/json", 0.9, Format::Json);
+///
+/// let best = |accept| negotiator.negotiate(&parse_accept(accept)).map(|o| o.value);
+///
+/// assert_eq!(best("text/html"), Some(Format::Html));
+/// // The server prefers plain text over JSON when both are equally acceptable
+/// assert_eq!(best("application/json, text/plain"), Some(Format::Plain));
+/// assert_eq!(best("image/png"), None);
+/// ```
+#[derive(Debug)]
+pub struct Negotiator<T> {
+    offers: Vec<Offer<T>>,
+}
+
+impl<T> Default for Negotiator<T> {
+    fn default() -> Self {
+        Negotiator { offers: Vec::new() }
+    }
+}
+
+impl<T> Negotiator<T> {
+    /// Creates a negotiator without any offers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an offer with a server-side quality of 1.0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `media_type` is not a valid media type.
+    pub fn offer(self, media_type: &str, value: T) -> Self {
+        self.offer_with_qs(media_type, 1.0, value)
+    }
+
+    /// Adds an offer with the given server-side quality.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `media_type` is not a valid media type or `qs` is not
+    /// between 0.0 and 1.0.
+    pub fn offer_with_qs(mut self, media_type: &str, qs: f32, value: T) -> Self {
+        let media_type = MediaType::try_from(media_type)
+            .unwrap_or_else(|e| panic!("invalid offered media type {media_type:?}: {e}"));
+        let qs = QValue::new(qs).expect("qs must be between 0.0 and 1.0");
+
+        self.offers.push(Offer {
+            media_type,
+            qs,
+            value,
+        });
+        self
+    }
+
+    /// Returns the offers in the order they were added.
+    pub fn offers(&self) -> &[Offer<T>] {
+        &self.offers
+    }
+
+    /// Returns the best offer for the parsed Accept header, or `None` if no
+    /// offer is acceptable.
+    pub fn negotiate(&self, directives: &[Directive]) -> Option<&Offer<T>> {
+        let offers = self.offers.iter().map(|o| (&o.media_type, o.qs));
+        select(directives, offers).map(|i| &self.offers[i])
+    }
+}
+
+/// Represents an Accept-Language header directive with a language range and
+/// quality value.
+///
+/// The range is a basic language range as defined in
+/// [RFC 4647 Section 2.1](https://www.rfc-editor.org/rfc/rfc4647#section-2.1),
+/// either `*` or a language tag prefix such as `de` or `de-CH`, or an
+/// extended language range with `*` subtags such as `de-*-DE`
+/// ([Section 2.2](https://www.rfc-editor.org/rfc/rfc4647#section-2.2)).
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::LanguageRange;
+/// use std::convert::TryFrom;
+///
+/// let range = LanguageRange::try_from("de-CH;q=0.8").unwrap();
+/// assert_eq!(range.range, "de-ch");
+/// assert_eq!(range.q, 0.8);
+/// assert!(range.matches("de-CH-1996"));
+/// assert!(!range.matches("de"));
+///
+/// let extended = LanguageRange::try_from("de-*-DE").unwrap();
+/// assert!(extended.matches("de-Latn-DE"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageRange {
+    /// The language range, lowercased
+    pub range: String,
+    /// Quality value (0 to 1), defaults to 1 if not specified
+    pub q: QValue,
+}
+
+impl LanguageRange {
+    /// Checks if a language tag matches this range.
+    ///
+    /// Basic ranges use basic filtering, following
+    /// [RFC 4647 Section 3.3.1](https://www.rfc-editor.org/rfc/rfc4647#section-3.3.1):
+    /// a tag matches if it equals the range or starts with the range followed
+    /// by `-`, ignoring case. The range `*` matches every tag. Ranges with
+    /// other `*` subtags use [`extended_filter()`].
+    pub fn matches(&self, tag: &str) -> bool {
+        if self.range == "*" {
+            return true;
+        }
+        if self.range.split('-').any(|subtag| subtag == "*") {
+            return extended_filter(&self.range, tag);
+        }
+
+        let tag = tag.to_ascii_lowercase();
+        match tag.strip_prefix(&self.range) {
+            Some(rest) => rest.is_empty() || rest.starts_with('-'),
+            None => false,
+        }
+    }
+
+    /// Returns the number of subtags in the range, `*` counting as none.
+    ///
+    /// Longer ranges are more specific; a tag takes its quality from the most
+    /// specific range that matches it.
+    pub fn specificity(&self) -> usize {
+        self.range
+            .split('-')
+            .filter(|subtag| *subtag != "*")
+            .count()
+    }
+
+    /// Returns the range with its last subtag removed, as in the lookup
+    /// scheme of
+    /// [RFC 4647 Section 3.4](https://www.rfc-editor.org/rfc/rfc4647#section-3.4),
+    /// or `None` if it has a single subtag.
+    ///
+    /// A single-character subtag left at the end, such as the `x` of a
+    /// private use sequence, is removed as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ip_info::content_negotiation::LanguageRange;
+    /// use std::convert::TryFrom;
+    ///
+    /// let range = LanguageRange::try_from("zh-Hant-CN-x-private1").unwrap();
+    /// let range = range.truncated().unwrap();
+    /// assert_eq!(range.range, "zh-hant-cn");
+    /// assert_eq!(range.truncated().unwrap().range, "zh-hant");
+    /// assert_eq!(LanguageRange::try_from("de").unwrap().truncated(), None);
+    /// ```
+    pub fn truncated(&self) -> Option<LanguageRange> {
+        let mut range = self.range.rsplit_once('-')?.0;
+        while let Some((rest, last)) = range.rsplit_once('-') {
+            if last.len() != 1 {
+                break;
+            }
+            range = rest;
+        }
+        Some(LanguageRange {
+            range: range.to_string(),
+            q: self.q,
+        })
+    }
+}
+
+impl TryFrom<&str> for LanguageRange {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (range, q) = weighted_token(value)?;
+
+        // `(1*8ALPHA / "*") *("-" (1*8alphanum / "*"))`
+        let is_valid = range.split('-').enumerate().all(|(i, subtag)| {
+            subtag == "*"
+                || (1..=8).contains(&subtag.len())
+                    && subtag.chars().all(|c| {
+                        if i == 0 {
+                            c.is_ascii_alphabetic()
+                        } else {
+                            c.is_ascii_alphanumeric()
+                        }
+                    })
+        });
+        if !is_valid {
+            return Err(ParseError::InvalidLanguageRange(range.to_string()));
+        }
+
+        Ok(LanguageRange {
+            range: range.to_ascii_lowercase(),
+            q,
+        })
+    }
+}
+
+/// Parses an Accept-Language header value into a sorted list of language ranges.
+///
+/// Like [`parse_accept()`], invalid ranges are skipped and the result is sorted
+/// by quality value in descending order, keeping the header order for equal
+/// quality values.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::parse_accept_language;
+///
+/// let ranges = parse_accept_language("fr;q=0.8, de-CH, de;q=0.9, *;q=0.1");
+/// let ranges: Vec<&str> = ranges.iter().map(|r| r.range.as_str()).collect();
+/// assert_eq!(ranges, vec!["de-ch", "de", "fr", "*"]);
+/// ```
+pub fn parse_accept_language(header_value: &str) -> Vec<LanguageRange> {
+    let mut r = split_list(header_value)
+        .into_iter()
+        .filter_map(|s| LanguageRange::try_from(s).ok())
+        .collect::<Vec<LanguageRange>>();
+    r.sort_by_key(|d| std::cmp::Reverse(d.q));
+    r
+}
+
+/// Checks if a language tag matches an extended language range.
+///
+/// Implements extended filtering as defined in
+/// [RFC 4647 Section 3.3.2](https://www.rfc-editor.org/rfc/rfc4647#section-3.3.2):
+/// `*` subtags match any sequence of subtags, and subtags missing from the
+/// range may be skipped in the tag, so `de-*-DE` matches `de-Latn-DE` and
+/// `de-DE` matches `de-Latn-DE`. Comparison ignores case.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::extended_filter;
+///
+/// assert!(extended_filter("de-*-DE", "de-Latn-DE"));
+/// assert!(extended_filter("de-DE", "de-Latn-DE-1996"));
+/// assert!(extended_filter("*", "pt-BR"));
+/// assert!(!extended_filter("de-DE", "de"));
+/// // Singletons (like the private use "x") cannot be skipped
+/// assert!(!extended_filter("de-DE", "de-x-DE"));
+/// ```
+pub fn extended_filter(range: &str, tag: &str) -> bool {
+    let range = range.to_ascii_lowercase();
+    let tag = tag.to_ascii_lowercase();
+    let mut range = range.split('-');
+    let mut tag = tag.split('-').peekable();
+
+    match (range.next(), tag.next()) {
+        (Some(r), Some(t)) if r == "*" || r == t => {}
+        _ => return false,
+    }
+
+    for subtag in range {
+        if subtag == "*" {
+            continue;
+        }
+        loop {
+            match tag.next() {
+                None => return false,
+                Some(t) if t == subtag => break,
+                Some(t) if t.len() == 1 => return false,
+                Some(_) => {}
+            }
+        }
+    }
+
+    true
+}
+
+/// Selects the available language the client prefers.
+///
+/// Each available language tag takes its quality from the most specific
+/// range in `ranges` that matches it by basic filtering (see
+/// [`LanguageRange::matches()`]), so `de-CH;q=0.5, de` ranks `de-CH` below
+/// `de-DE`. Tags with a quality of 0 are not acceptable. Ties are broken by
+/// the order of the ranges, then by the order of `available`.
+///
+/// If no tag is acceptable, the acceptable ranges are shortened by a subtag
+/// (see [`LanguageRange::truncated()`]) and matched again, until a tag is
+/// found or no range can be shortened, like the lookup fallback of
+/// [RFC 4647 Section 3.4](https://www.rfc-editor.org/rfc/rfc4647#section-3.4).
+/// Ranges with a quality of 0 keep excluding their tags.
+///
+/// Returns the index into `available` of the selected tag, or `None` if none
+/// is acceptable.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::{negotiate_language, parse_accept_language};
+///
+/// let available = ["en", "de", "pt-BR"];
+///
+/// let ranges = parse_accept_language("de-DE, de;q=0.9, en;q=0.8");
+/// assert_eq!(negotiate_language(&ranges, &available), Some(1));
+///
+/// let ranges = parse_accept_language("pt, *;q=0.1");
+/// assert_eq!(negotiate_language(&ranges, &available), Some(2));
+///
+/// // `pt-PT` falls back to `pt`
+/// let ranges = parse_accept_language("pt-PT");
+/// assert_eq!(negotiate_language(&ranges, &available), Some(2));
+///
+/// let ranges = parse_accept_language("fr");
+/// assert_eq!(negotiate_language(&ranges, &available), None);
+/// ```
+pub fn negotiate_language(ranges: &[LanguageRange], available: &[&str]) -> Option<usize> {
+    let excluded: Vec<LanguageRange> = ranges.iter().filter(|r| r.q.is_zero()).cloned().collect();
+    let mut ranges = ranges.to_vec();
+    loop {
+        if let Some(i) = filter_language(&ranges, available) {
+            return Some(i);
+        }
+
+        let truncated: Vec<LanguageRange> = ranges
+            .iter()
+            .filter(|r| !r.q.is_zero())
+            .filter_map(LanguageRange::truncated)
+            .collect();
+        if truncated.is_empty() {
+            return None;
+        }
+        ranges = excluded.iter().cloned().chain(truncated).collect();
+    }
+}
+
+/// Selects a language tag by filtering alone, as [`negotiate_language()`]
+/// does before falling back to shorter ranges.
+fn filter_language(ranges: &[LanguageRange], available: &[&str]) -> Option<usize> {
+    available
+        .iter()
+        .enumerate()
+        .filter_map(|(i, tag)| {
+            let (range_index, range) = ranges
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.matches(tag))
+                .min_by_key(|(j, r)| (std::cmp::Reverse(r.specificity()), *j))?;
+            (!range.q.is_zero()).then_some((i, range.q, range_index))
+        })
+        .min_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)).then(a.0.cmp(&b.0)))
+        .map(|(i, _, _)| i)
+}
+
+/// Represents an Accept-Encoding header directive with a content coding and
+/// quality value.
+///
+/// The coding is lowercased; the deprecated alias `x-gzip` is read as `gzip`.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::Coding;
+/// use std::convert::TryFrom;
+///
+/// let coding = Coding::try_from("X-GZIP;q=0.5").unwrap();
+/// assert_eq!(coding.coding, "gzip");
+/// assert_eq!(coding.q, 0.5);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coding {
+    /// The content coding (e.g., "gzip", "br", "identity" or "*")
+    pub coding: String,
+    /// Quality value (0 to 1), defaults to 1 if not specified
+    pub q: QValue,
+}
+
+impl TryFrom<&str> for Coding {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (coding, q) = weighted_token(value)?;
+        if !is_token(coding) {
+            return Err(ParseError::InvalidCoding(coding.to_string()));
+        }
+
+        let coding = match coding.to_ascii_lowercase().as_str() {
+            "x-gzip" => "gzip".to_string(),
+            other => other.to_string(),
+        };
+
+        Ok(Coding { coding, q })
+    }
+}
+
+/// Parses an Accept-Encoding header value into a sorted list of codings.
+///
+/// Like [`parse_accept()`], invalid codings are skipped and the result is
+/// sorted by quality value in descending order. An empty header value yields
+/// an empty list, meaning only `identity` is acceptable.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::parse_accept_encoding;
+///
+/// let codings = parse_accept_encoding("gzip;q=0.8, br, identity;q=0");
+/// let codings: Vec<&str> = codings.iter().map(|c| c.coding.as_str()).collect();
+/// assert_eq!(codings, vec!["br", "gzip", "identity"]);
+/// ```
+pub fn parse_accept_encoding(header_value: &str) -> Vec<Coding> {
+    let mut r = split_list(header_value)
+        .into_iter()
+        .filter_map(|s| Coding::try_from(s).ok())
+        .collect::<Vec<Coding>>();
+    r.sort_by_key(|d| std::cmp::Reverse(d.q));
+    r
+}
+
+/// Selects the available content coding the client prefers.
+///
+/// Each coding takes its quality from the directive naming it, or from `*` if
+/// it is not named. `identity` is acceptable with quality 1.0 unless it is
+/// excluded by `identity;q=0` or by `*;q=0` without an `identity` directive;
+/// other codings are only acceptable if listed. Ties are broken by the order
+/// of `available`, since clients commonly list codings they support with
+/// equal quality in no particular order.
+///
+/// Returns the index into `available` of the selected coding, or `None` if
+/// none is acceptable.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::{negotiate_encoding, parse_accept_encoding};
+///
+/// let available = ["br", "gzip", "identity"];
+///
+/// let codings = parse_accept_encoding("gzip, deflate, br");
+/// assert_eq!(negotiate_encoding(&codings, &available), Some(0));
+///
+/// // Only identity is acceptable if nothing else is listed
+/// assert_eq!(negotiate_encoding(&[], &available), Some(2));
+///
+/// let codings = parse_accept_encoding("*;q=0");
+/// assert_eq!(negotiate_encoding(&codings, &available), None);
+/// ```
+pub fn negotiate_encoding(codings: &[Coding], available: &[&str]) -> Option<usize> {
+    let quality = |name: &str| {
+        let explicit = codings.iter().find(|c| c.coding.eq_ignore_ascii_case(name));
+        let wildcard = codings.iter().find(|c| c.coding == "*");
+        match (explicit, wildcard) {
+            (Some(c), _) | (None, Some(c)) => c.q,
+            (None, None) if name.eq_ignore_ascii_case("identity") => QValue::ONE,
+            (None, None) => QValue::ZERO,
+        }
+    };
+
+    available
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (i, quality(name)))
+        .filter(|(_, q)| !q.is_zero())
+        .min_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)))
+        .map(|(i, _)| i)
+}
+
+/// Represents an Accept-Charset header directive with a charset and quality
+/// value.
+///
+/// The charset is lowercased, and common aliases of UTF-8, ISO-8859-1 and
+/// US-ASCII are replaced by their preferred MIME names.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::CharsetRange;
+/// use std::convert::TryFrom;
+///
+/// let range = CharsetRange::try_from("Latin1;q=0.7").unwrap();
+/// assert_eq!(range.charset, "iso-8859-1");
+/// assert_eq!(range.q, 0.7);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharsetRange {
+    /// The charset (e.g., "utf-8", "iso-8859-1" or "*")
+    pub charset: String,
+    /// Quality value (0 to 1), defaults to 1 if not specified
+    pub q: QValue,
+}
+
+impl CharsetRange {
+    fn new(charset: &str, q: QValue) -> Result<Self, ParseError> {
+        if !is_token(charset) {
+            return Err(ParseError::InvalidCharset(charset.to_string()));
+        }
+
+        let charset = charset.to_ascii_lowercase();
+        let charset = match charset.as_str() {
+            "utf8" => "utf-8",
+            "latin1" | "l1" | "iso_8859-1" | "iso8859-1" | "iso-ir-100" | "cp819" => "iso-8859-1",
+            "ascii" | "us" | "iso646-us" | "ansi_x3.4-1968" | "cp367" => "us-ascii",
+            other => other,
+        };
+
+        Ok(CharsetRange {
+            charset: charset.to_string(),
+            q,
+        })
+    }
+}
+
+impl TryFrom<&str> for CharsetRange {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (charset, q) = weighted_token(value)?;
+        CharsetRange::new(charset, q)
+    }
+}
+
+/// Parses an Accept-Charset header value into a sorted list of charsets.
+///
+/// Like [`parse_accept()`], invalid charsets are skipped and the result is
+/// sorted by quality value in descending order.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::content_negotiation::parse_accept_charset;
+///
+/// let ranges = parse_accept_charset("iso-8859-5, unicode-1-1;q=0.8, *;q=0.1");
+/// let ranges: Vec<&str> = ranges.iter().map(|r| r.charset.as_str()).collect();
+/// assert_eq!(ranges, vec!["iso-8859-5", "unicode-1-1", "*"]);
+/// ```
+pub fn parse_accept_charset(header_value: &str) -> Vec<CharsetRange> {
+    let mut r = split_list(header_value)
+        .into_iter()
+        .filter_map(|s| CharsetRange::try_from(s).ok())
+        .collect::<Vec<CharsetRange>>();
+    r.sort_by_key(|d| std::cmp::Reverse(d.q));
     r
 }
 
-/// Parses a complete HTTP Accept header value into a sorted list of directives.
+/// Returns the charsets requested by the `charset` parameter of the Accept
+/// directives including a media type.
 ///
-/// This is the strict version that returns an error if any directive fails to parse.
+/// For `text/plain;charset=us-ascii, text/*;charset=iso-8859-1;q=0.5`, the
+/// charsets for `text/plain` are US-ASCII with quality 1.0 and ISO-8859-1
+/// with quality 0.5. Invalid charsets are skipped.
 ///
-/// # Arguments
+/// # Examples
 ///
-/// * `header_value` - The complete Accept header value as a string slice
+/// ```
+/// use ip_info::content_negotiation::{charset_ranges, parse_accept, MediaType};
+/// use std::convert::TryFrom;
 ///
-/// # Returns
+/// let directives = parse_accept("text/plain;charset=us-ascii, text/html;charset=utf-8");
+/// let plain = MediaType::try_from("text/plain").unwrap();
 ///
-/// A `Result<Vec<Directive>, ParseError>` containing all parsed directives sorted by
-/// quality value in descending order, or an error if any directive is invalid.
+/// let ranges = charset_ranges(&directives, &plain);
+/// assert_eq!(ranges.len(), 1);
+/// assert_eq!(ranges[0].charset, "us-ascii");
+/// ```
+pub fn charset_ranges(directives: &[Directive], media_type: &MediaType) -> Vec<CharsetRange> {
+    directives
+        .iter()
+        .filter(|d| d.media_type.includes(media_type))
+        .filter_map(|d| {
+            let (_, charset) = d
+                .media_type
+                .parameters
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("charset"))?;
+            CharsetRange::new(charset, d.q).ok()
+        })
+        .collect()
+}
+
+/// Orders the available charsets by the client's preference.
 ///
-/// # Errors
+/// Each charset takes its quality from the range naming it, or from `*` if
+/// it is not named; unlisted charsets are not acceptable. If `ranges` is
+/// empty, every charset is acceptable. Charsets with a quality of 0 are left
+/// out, and ties are broken by the order of `available`.
 ///
-/// Returns the first `ParseError` encountered when parsing directives.
+/// Returns the indices into `available` of the acceptable charsets, most
+/// preferred first. Unlike the other negotiation functions this returns every
+/// acceptable choice, as the server may not be able to represent its
+/// response in the preferred charset.
 ///
 /// # Examples
 ///
 /// ```
-/// use ip_info::content_negotiation::try_parse_accept;
+/// use ip_info::content_negotiation::{parse_accept_charset, rank_charsets};
 ///
-/// let accept = "text/html;q=0.9,application/json;q=1.0,text/plain;q=0.8";
-/// let directives = try_parse_accept(accept).unwrap();
+/// let available = ["utf-8", "iso-8859-1", "us-ascii"];
 ///
-/// // Results are sorted by quality value (highest first)
-/// assert_eq!(directives[0].media_type.sub_type, "json"); // q=1.0
-/// assert_eq!(directives[1].media_type.sub_type, "html"); // q=0.9
-/// assert_eq!(directives[2].media_type.sub_type, "plain"); // q=0.8
+/// let ranges = parse_accept_charset("us-ascii, iso-8859-1;q=0.5");
+/// assert_eq!(rank_charsets(&ranges, &available), vec![2, 1]);
 ///
-/// // Error case
-/// assert!(try_parse_accept("text/html,invalid,application/json").is_err());
+/// assert_eq!(rank_charsets(&[], &available), vec![0, 1, 2]);
+///
+/// let ranges = parse_accept_charset("koi8-r");
+/// assert!(rank_charsets(&ranges, &available).is_empty());
 /// ```
-pub fn try_parse_accept(header_value: &str) -> Result<Vec<Directive>, ParseError> {
-    let mut r = header_value
-        .split(',')
-        .map(|s| parse_accept_directive(s.trim()))
-        .collect::<Result<Vec<Directive>, ParseError>>()?;
-    r.sort_by(|a, b| b.q.partial_cmp(&a.q).unwrap());
-    Ok(r)
+pub fn rank_charsets(ranges: &[CharsetRange], available: &[&str]) -> Vec<usize> {
+    let quality = |name: &str| {
+        if ranges.is_empty() {
+            return QValue::ONE;
+        }
+        ranges
+            .iter()
+            .find(|r| r.charset.eq_ignore_ascii_case(name))
+            .or_else(|| ranges.iter().find(|r| r.charset == "*"))
+            .map_or(QValue::ZERO, |r| r.q)
+    };
+
+    let mut ranked: Vec<(usize, QValue)> = available
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (i, quality(name)))
+        .filter(|(_, q)| !q.is_zero())
+        .collect();
+    ranked.sort_by_key(|&(_, q)| std::cmp::Reverse(q));
+    ranked.into_iter().map(|(i, _)| i).collect()
 }
 
 #[cfg(test)]
@@ -621,7 +1874,7 @@ mod tests {
     #[test]
     fn test_q_value_precision() {
         let directive = parse_accept_directive("text/html;q=0.123").unwrap();
-        assert!((directive.q - 0.123).abs() < f32::EPSILON);
+        assert_eq!(directive.q, QValue::from_thousandths(123).unwrap());
     }
 
     #[test]
@@ -674,12 +1927,14 @@ mod tests {
             parse_accept_directive("text/html;charset=utf-8;q=0.8;boundary=test").unwrap();
         assert_eq!(directive.media_type.main_type, "text");
         assert_eq!(directive.media_type.sub_type, "html");
+        // Parameters after q are accept extensions, not media type parameters
         assert_eq!(
             directive.media_type.parameters,
-            vec![
-                ("charset".to_string(), "utf-8".to_string()),
-                ("boundary".to_string(), "test".to_string())
-            ]
+            vec![("charset".to_string(), "utf-8".to_string())]
+        );
+        assert_eq!(
+            directive.extensions,
+            vec![("boundary".to_string(), Some("test".to_string()))]
         );
         assert_eq!(directive.q, 0.8);
     }
@@ -687,7 +1942,7 @@ mod tests {
     #[test]
     fn test_parameter_whitespace_trimming() {
         let media_type =
-            MediaType::try_from("text/html; charset = utf-8 ; boundary = something").unwrap();
+            MediaType::try_from("text/html ; charset=utf-8 ;\tboundary=something").unwrap();
         assert_eq!(media_type.main_type, "text");
         assert_eq!(media_type.sub_type, "html");
         assert_eq!(
@@ -697,6 +1952,16 @@ mod tests {
                 ("boundary".to_string(), "something".to_string())
             ]
         );
+
+        // Whitespace around '=' is not allowed by RFC 9110
+        assert_eq!(
+            MediaType::try_from("text/html; charset = utf-8"),
+            Err(ParseError::UnexpectedCharacter(' ', 18))
+        );
+        assert_eq!(
+            MediaType::try_from("text/html; charset= utf-8"),
+            Err(ParseError::EmptyParameterValue("charset".to_string()))
+        );
     }
 
     #[test]
@@ -709,10 +1974,536 @@ mod tests {
 
     #[test]
     fn test_parameter_without_value() {
-        let media_type = MediaType::try_from("text/html;charset").unwrap();
+        assert_eq!(
+            MediaType::try_from("text/html;charset"),
+            Err(ParseError::MissingParameterValue("charset".to_string()))
+        );
+        assert_eq!(
+            MediaType::try_from("text/html;charset;q=1"),
+            Err(ParseError::MissingParameterValue("charset".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_media_type_case_insensitive_names() {
+        let media_type = MediaType::try_from("Text/HTML;Charset=UTF-8").unwrap();
         assert_eq!(media_type.main_type, "text");
         assert_eq!(media_type.sub_type, "html");
-        // Parameters without = are filtered out
-        assert_eq!(media_type.parameters, vec![]);
+        assert_eq!(
+            media_type.parameters,
+            vec![("charset".to_string(), "UTF-8".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_media_type_grammar_errors() {
+        assert_eq!(MediaType::try_from(""), Err(ParseError::EmptyType));
+        assert_eq!(MediaType::try_from("text/"), Err(ParseError::EmptySubtype));
+        assert_eq!(
+            MediaType::try_from("text/ html"),
+            Err(ParseError::UnexpectedCharacter(' ', 5))
+        );
+        assert_eq!(
+            MediaType::try_from("text/html x"),
+            Err(ParseError::UnexpectedCharacter('x', 10))
+        );
+        assert_eq!(
+            MediaType::try_from("text/html;=utf-8"),
+            Err(ParseError::EmptyParameterName)
+        );
+        assert_eq!(
+            MediaType::try_from("te(x)t/html"),
+            Err(ParseError::UnexpectedCharacter('(', 2))
+        );
+        assert_eq!(
+            MediaType::try_from("text/html;a=\"b"),
+            Err(ParseError::UnterminatedQuotedString)
+        );
+        assert_eq!(
+            MediaType::try_from("text/html;a=b\"c\""),
+            Err(ParseError::UnexpectedCharacter('"', 13))
+        );
+        assert_eq!(
+            MediaType::try_from("text/html;a=\"\x01\""),
+            Err(ParseError::UnexpectedCharacter('\x01', 13))
+        );
+    }
+
+    #[test]
+    fn test_media_type_quoted_pair() {
+        let media_type = MediaType::try_from(r#"text/plain;a="x\"y\\z";b="""#).unwrap();
+        assert_eq!(
+            media_type.parameters,
+            vec![
+                ("a".to_string(), r#"x"y\z"#.to_string()),
+                ("b".to_string(), String::new())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_media_type_display_round_trip() {
+        for input in [
+            "text/html",
+            "*/*",
+            "text/plain;format=flowed;charset=utf-8",
+            r#"text/plain;a="x y";b="";c="q\"\\""#,
+        ] {
+            let media_type = MediaType::try_from(input).unwrap();
+            assert_eq!(media_type.to_string(), input);
+            assert_eq!(MediaType::try_from(input).unwrap(), media_type);
+        }
+
+        let media_type = MediaType::try_from(r#"TEXT/Plain ; A="token""#).unwrap();
+        assert_eq!(media_type.to_string(), "text/plain;a=token");
+    }
+
+    #[test]
+    fn test_parse_accept_quoted_commas() {
+        let directives = parse_accept(r#"text/plain;title="a, b;q=0";q=0.5, text/html"#);
+        assert_eq!(directives.len(), 2);
+        assert_eq!(directives[1].media_type.parameters[0].1, "a, b;q=0");
+        assert_eq!(directives[1].q, 0.5);
+    }
+
+    #[test]
+    fn test_try_parse_accept_empty_elements() {
+        let directives = try_parse_accept(" , text/html,,").unwrap();
+        assert_eq!(directives.len(), 1);
+        assert_eq!(
+            try_parse_accept("text/html, text /plain").unwrap_err(),
+            ParseError::UnexpectedCharacter(' ', 4)
+        );
+    }
+
+    fn offers(types: &[&str]) -> Vec<MediaType> {
+        types
+            .iter()
+            .map(|t| MediaType::try_from(*t).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_includes_parameters() {
+        let range = MediaType::try_from("text/plain;Format=flowed").unwrap();
+        let offer = MediaType::try_from("text/plain;format=flowed").unwrap();
+        let other = MediaType::try_from("text/plain;format=fixed").unwrap();
+        assert!(range.includes(&offer));
+        assert!(!range.includes(&other));
+    }
+
+    #[test]
+    fn test_specificity_wildcards() {
+        assert_eq!(MediaType::try_from("*/*").unwrap().specificity(), 0);
+        assert_eq!(MediaType::try_from("text/*").unwrap().specificity(), 1);
+        assert_eq!(MediaType::try_from("text/html").unwrap().specificity(), 2);
+        assert_eq!(
+            MediaType::try_from("text/html;level=1")
+                .unwrap()
+                .specificity(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_negotiate_specific_overrides_wildcard() {
+        let offers = offers(&["text/plain", "text/html", "application/json"]);
+        let directives = parse_accept("*/*;q=0.9, text/html;q=0.9");
+        // Both plain and html get q=0.9, but */* comes first in the header
+        assert_eq!(negotiate(&directives, &offers), Some(0));
+
+        let directives = parse_accept("text/html;q=0.9, */*;q=0.9");
+        assert_eq!(negotiate(&directives, &offers), Some(1));
+    }
+
+    #[test]
+    fn test_negotiate_specific_lowers_quality() {
+        let offers = offers(&["text/plain", "text/html"]);
+        let directives = parse_accept("text/*, text/plain;q=0.5");
+        assert_eq!(negotiate(&directives, &offers), Some(1));
+    }
+
+    #[test]
+    fn test_negotiate_q_zero_excludes() {
+        let offers = offers(&["text/plain", "text/html", "application/json"]);
+        let directives = parse_accept("*/*, text/plain;q=0");
+        assert_eq!(negotiate(&directives, &offers), Some(1));
+
+        let directives = parse_accept("text/plain;q=0");
+        assert_eq!(negotiate(&directives, &offers), None);
+    }
+
+    #[test]
+    fn test_negotiate_parameters_take_precedence() {
+        let offers = offers(&["text/html;level=1", "text/html;level=2"]);
+        let directives = parse_accept("text/html;q=0.5, text/html;level=2");
+        assert_eq!(negotiate(&directives, &offers), Some(1));
+    }
+
+    #[test]
+    fn test_negotiate_server_order_on_full_tie() {
+        let offers = offers(&["application/json", "text/plain"]);
+        let directives = parse_accept("*/*");
+        assert_eq!(negotiate(&directives, &offers), Some(0));
+    }
+
+    #[test]
+    fn test_negotiate_nothing_matches() {
+        let offers = offers(&["text/plain", "application/json"]);
+        assert_eq!(negotiate(&parse_accept("image/png"), &offers), None);
+        assert_eq!(negotiate(&[], &offers), None);
+    }
+
+    #[test]
+    fn test_negotiate_browser_accept() {
+        let offers = offers(&["text/plain", "text/html", "application/json"]);
+        let directives = parse_accept(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+        );
+        assert_eq!(negotiate(&directives, &offers), Some(1));
+    }
+
+    #[test]
+    fn test_negotiator_qs_scales_client_quality() {
+        let negotiator = Negotiator::new()
+            .offer_with_qs("text/html", 0.5, "html")
+            .offer("application/json", "json");
+
+        // 1.0 * 0.5 < 0.8 * 1.0
+        let directives = parse_accept("text/html, application/json;q=0.8");
+        assert_eq!(negotiator.negotiate(&directives).unwrap().value, "json");
+
+        // 1.0 * 0.5 > 0.4 * 1.0
+        let directives = parse_accept("text/html, application/json;q=0.4");
+        assert_eq!(negotiator.negotiate(&directives).unwrap().value, "html");
+    }
+
+    #[test]
+    fn test_negotiator_qs_zero_never_selected() {
+        let negotiator = Negotiator::new().offer_with_qs("text/plain", 0.0, ());
+        assert!(negotiator.negotiate(&parse_accept("text/plain")).is_none());
+    }
+
+    #[test]
+    fn test_negotiator_offers_in_order() {
+        let negotiator = Negotiator::new()
+            .offer("text/plain", 1)
+            .offer("text/html", 2);
+        let types: Vec<String> = negotiator
+            .offers()
+            .iter()
+            .map(|o| o.media_type.sub_type.clone())
+            .collect();
+        assert_eq!(types, vec!["plain", "html"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid offered media type")]
+    fn test_negotiator_invalid_offer_panics() {
+        let _ = Negotiator::new().offer("plain", ());
+    }
+
+    #[test]
+    fn test_language_range_invalid() {
+        for range in [
+            "",
+            "toolonglanguage",
+            "1de",
+            "de_DE",
+            "de-",
+            "de-*x",
+            "de--DE",
+        ] {
+            assert_eq!(
+                LanguageRange::try_from(range),
+                Err(ParseError::InvalidLanguageRange(range.to_string()))
+            );
+        }
+        assert!(matches!(
+            LanguageRange::try_from("de;q=2"),
+            Err(ParseError::InvalidQualityValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_language_range_matches() {
+        let range = LanguageRange::try_from("EN").unwrap();
+        assert!(range.matches("en"));
+        assert!(range.matches("en-US"));
+        assert!(!range.matches("eng"));
+
+        let wildcard = LanguageRange::try_from("*").unwrap();
+        assert!(wildcard.matches("de"));
+        assert_eq!(wildcard.specificity(), 0);
+    }
+
+    #[test]
+    fn test_parse_accept_language_skips_invalid() {
+        let ranges = parse_accept_language("de, en_US, fr;q=0.5");
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[1].range, "fr");
+    }
+
+    #[test]
+    fn test_extended_filter() {
+        assert!(extended_filter("de-de", "de-DE"));
+        assert!(extended_filter("*-DE", "de-Latn-DE"));
+        assert!(extended_filter("de-*", "de"));
+        assert!(!extended_filter("de-DE", "en-DE"));
+        assert!(!extended_filter("en", ""));
+    }
+
+    #[test]
+    fn test_negotiate_language_specific_range_wins() {
+        let available = ["de-DE", "de-CH"];
+        let ranges = parse_accept_language("de-CH;q=0.5, de");
+        assert_eq!(negotiate_language(&ranges, &available), Some(0));
+    }
+
+    #[test]
+    fn test_negotiate_language_extended_range() {
+        let available = ["en", "de-Latn-DE", "de-CH"];
+        let ranges = parse_accept_language("de-*-DE, en;q=0.5");
+        assert_eq!(ranges[0].range, "de-*-de");
+        assert_eq!(negotiate_language(&ranges, &available), Some(1));
+
+        // The wildcard does not count towards specificity
+        let ranges = parse_accept_language("*-CH;q=0.1, *;q=0.9");
+        assert_eq!(ranges[1].specificity(), 1);
+        assert_eq!(negotiate_language(&ranges, &available), Some(0));
+    }
+
+    #[test]
+    fn test_negotiate_language_q_zero_excludes() {
+        let available = ["en", "de"];
+        let ranges = parse_accept_language("*, en;q=0");
+        assert_eq!(negotiate_language(&ranges, &available), Some(1));
+    }
+
+    #[test]
+    fn test_negotiate_language_lookup_fallback() {
+        let available = ["en", "de", "zh-Hant"];
+        let ranges = parse_accept_language("de-AT-1996, en;q=0");
+        assert_eq!(negotiate_language(&ranges, &available), Some(1));
+        let ranges = parse_accept_language("zh-Hant-TW-x-foo");
+        assert_eq!(negotiate_language(&ranges, &available), Some(2));
+
+        // Filtering takes precedence over shorter ranges
+        let ranges = parse_accept_language("de-AT, en;q=0.1");
+        assert_eq!(negotiate_language(&ranges, &available), Some(0));
+
+        // Excluded tags stay excluded
+        let ranges = parse_accept_language("de-AT, de;q=0");
+        assert_eq!(negotiate_language(&ranges, &available), None);
+    }
+
+    #[test]
+    fn test_negotiate_language_ties() {
+        let available = ["en", "de", "fr"];
+        let ranges = parse_accept_language("fr, de");
+        assert_eq!(negotiate_language(&ranges, &available), Some(2));
+        let ranges = parse_accept_language("*");
+        assert_eq!(negotiate_language(&ranges, &available), Some(0));
+        assert_eq!(negotiate_language(&[], &available), None);
+    }
+
+    #[test]
+    fn test_coding_invalid() {
+        assert_eq!(
+            Coding::try_from("g zip"),
+            Err(ParseError::UnexpectedCharacter('z', 2))
+        );
+        assert_eq!(
+            Coding::try_from("gz(ip)"),
+            Err(ParseError::UnexpectedCharacter('(', 2))
+        );
+        assert_eq!(
+            Coding::try_from(";q=1"),
+            Err(ParseError::InvalidCoding(String::new()))
+        );
+        assert!(matches!(
+            Coding::try_from("gzip;q=-1"),
+            Err(ParseError::InvalidQualityValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_accept_encoding_empty() {
+        assert!(parse_accept_encoding("").is_empty());
+        assert!(parse_accept_encoding(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_negotiate_encoding_wildcard() {
+        let available = ["zstd", "gzip", "identity"];
+        let codings = parse_accept_encoding("gzip;q=0.5, *");
+        assert_eq!(negotiate_encoding(&codings, &available), Some(0));
+
+        let codings = parse_accept_encoding("gzip;q=0.5, *;q=0.1");
+        assert_eq!(negotiate_encoding(&codings, &available), Some(1));
+    }
+
+    #[test]
+    fn test_negotiate_encoding_identity_excluded() {
+        let available = ["br", "identity"];
+        let codings = parse_accept_encoding("identity;q=0");
+        assert_eq!(negotiate_encoding(&codings, &available), None);
+
+        // An explicit identity directive overrides *;q=0
+        let codings = parse_accept_encoding("*;q=0, identity;q=0.5");
+        assert_eq!(negotiate_encoding(&codings, &available), Some(1));
+    }
+
+    #[test]
+    fn test_negotiate_encoding_quality_beats_server_order() {
+        let available = ["br", "gzip", "identity"];
+        let codings = parse_accept_encoding("br;q=0.5, gzip");
+        assert_eq!(negotiate_encoding(&codings, &available), Some(1));
+    }
+
+    #[test]
+    fn test_includes_ignores_charset() {
+        let range = MediaType::try_from("text/plain;charset=us-ascii").unwrap();
+        let offer = MediaType::try_from("text/plain").unwrap();
+        assert!(range.includes(&offer));
+    }
+
+    #[test]
+    fn test_charset_parameter_not_more_specific() {
+        let offers = [MediaType::try_from("text/plain").unwrap()];
+        let directives = parse_accept("text/plain;charset=iso-8859-1;q=0.5, text/plain");
+        assert_eq!(
+            quality_of(&directives, &offers[0]).map(|(q, _)| q),
+            Some(QValue::ONE)
+        );
+        assert_eq!(negotiate(&directives, &offers), Some(0));
+    }
+
+    #[test]
+    fn test_charset_range_invalid() {
+        assert_eq!(
+            CharsetRange::try_from(""),
+            Err(ParseError::InvalidCharset(String::new()))
+        );
+        assert_eq!(parse_accept_charset("utf 8, ascii").len(), 1);
+    }
+
+    #[test]
+    fn test_charset_ranges_quality_and_quotes() {
+        let directives = parse_accept("text/*;charset=\"Latin1\";q=0.5, application/json");
+        let plain = MediaType::try_from("text/plain").unwrap();
+        assert_eq!(
+            charset_ranges(&directives, &plain),
+            vec![CharsetRange {
+                charset: "iso-8859-1".to_string(),
+                q: QValue::from_thousandths(500).unwrap()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rank_charsets_wildcard() {
+        let available = ["utf-8", "iso-8859-1", "us-ascii"];
+        let ranges = parse_accept_charset("us-ascii;q=0.9, *;q=0.5, utf-8;q=0");
+        assert_eq!(rank_charsets(&ranges, &available), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_qvalue_grammar() {
+        for (input, thousandths) in [
+            ("0", 0),
+            ("0.", 0),
+            ("0.5", 500),
+            ("0.05", 50),
+            ("0.123", 123),
+            ("1", 1000),
+            ("1.", 1000),
+            ("1.000", 1000),
+        ] {
+            assert_eq!(input.parse::<QValue>().unwrap().thousandths(), thousandths);
+        }
+
+        for input in [
+            "", "7", "-1", "NaN", "inf", "0.12345", "1.001", "1.5", ".5", "00.5", "0.5e1", "+1",
+            "0,5",
+        ] {
+            assert_eq!(
+                input.parse::<QValue>(),
+                Err(ParseError::InvalidQualityValue(input.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_qvalue_display() {
+        for q in ["0", "0.001", "0.05", "0.5", "0.999", "1"] {
+            assert_eq!(q.parse::<QValue>().unwrap().to_string(), q);
+        }
+        assert_eq!("1.000".parse::<QValue>().unwrap().to_string(), "1");
+    }
+
+    #[test]
+    fn test_qvalue_new() {
+        assert_eq!(QValue::new(0.9), QValue::from_thousandths(900));
+        assert_eq!(QValue::new(1.5), None);
+        assert_eq!(QValue::new(f32::NAN), None);
+        assert_eq!(QValue::from_thousandths(1001), None);
+    }
+
+    #[test]
+    fn test_parse_accept_invalid_q_skipped() {
+        let directives =
+            parse_accept("text/html;q=NaN, text/plain;q=7, application/json;q=-1, */*");
+        assert_eq!(directives.len(), 1);
+        assert_eq!(directives[0].media_type.main_type, "*");
+    }
+
+    #[test]
+    fn test_parse_accept_directive_quoted_q_rejected() {
+        assert!(matches!(
+            parse_accept_directive(r#"text/html;q="0.5""#),
+            Err(ParseError::InvalidQualityValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_accept_directive_extension_errors() {
+        assert_eq!(
+            parse_accept_directive("text/html;q=0.5;ext=").unwrap_err(),
+            ParseError::EmptyParameterValue("ext".to_string())
+        );
+        assert_eq!(
+            parse_accept_directive("text/html;q=0.5;=x").unwrap_err(),
+            ParseError::EmptyParameterName
+        );
+        let directive = parse_accept_directive("text/html;q=0.5;EXT").unwrap();
+        assert_eq!(directive.extensions, vec![("ext".to_string(), None)]);
+    }
+
+    #[test]
+    fn test_other_headers_validate_q() {
+        assert!(LanguageRange::try_from("de;q=0.1234").is_err());
+        assert!(Coding::try_from("gzip;q=1.1").is_err());
+        assert!(CharsetRange::try_from("utf-8;Q=0.5").unwrap().q == 0.5);
+    }
+
+    #[test]
+    fn test_other_headers_share_the_grammar() {
+        // Whitespace is only allowed around ';', and parameters need a value
+        assert!(LanguageRange::try_from("de;q = 0.5").is_err());
+        assert!(Coding::try_from("gzip;q= 0.5").is_err());
+        assert!(CharsetRange::try_from("utf-8;flag").is_err());
+        assert!(LanguageRange::try_from("de;q=\"0.5\"").is_err());
+
+        let coding = Coding::try_from("gzip ; level=\"a;b\" ; q=0.5").unwrap();
+        assert_eq!(
+            (coding.coding.as_str(), coding.q),
+            ("gzip", QValue::new(0.5).unwrap())
+        );
+
+        // Commas in quoted-strings do not split the list
+        let ranges = parse_accept_charset("utf-8;x=\"a,b\";q=0.5, us-ascii");
+        let ranges: Vec<&str> = ranges.iter().map(|r| r.charset.as_str()).collect();
+        assert_eq!(ranges, vec!["us-ascii", "utf-8"]);
     }
 }
@@ -0,0 +1,360 @@
+//! Cross-origin access to the API: CORS and JSONP.
+//!
+//! [`CorsPolicy`] configures which origins may read responses from scripts,
+//! and builds the [`CorsLayer`] answering `OPTIONS` preflight requests and
+//! adding the `Access-Control-*` response headers.
+//!
+//! For clients that cannot use CORS, the [`jsonp`] middleware wraps JSON
+//! responses in a call to the function named by the `callback` query
+//! parameter.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::cors::{is_valid_callback, CorsPolicy};
+//!
+//! let policy: CorsPolicy = "https://example.com, https://example.org".parse().unwrap();
+//! assert!(policy.allows("https://example.org"));
+//! assert!(!policy.allows("https://evil.example"));
+//!
+//! assert!(is_valid_callback("jQuery123.handle_ip"));
+//! assert!(!is_valid_callback("alert(document.cookie)//"));
+//! ```
+
+use std::{str::FromStr, time::Duration};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Query, Request},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use thiserror::Error;
+use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer};
+
+use crate::negotiated::AcceptOverride;
+
+/// How long browsers may cache a preflight response.
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest accepted JSONP callback name.
+const MAX_CALLBACK_LEN: usize = 128;
+
+lazy_static! {
+    /// A JavaScript identifier or a dotted path of identifiers, ASCII only.
+    static ref CALLBACK: Regex =
+        Regex::new(r"^[A-Za-z_$][A-Za-z0-9_$]*(\.[A-Za-z_$][A-Za-z0-9_$]*)*$").unwrap();
+}
+
+/// Reserved words that cannot name a callback.
+const RESERVED_WORDS: &[&str] = &[
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "new",
+    "null",
+    "return",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+    "let",
+    "static",
+    "enum",
+    "await",
+];
+
+/// Errors that can occur when building a CORS policy from configuration.
+#[derive(Error, Debug, PartialEq)]
+pub enum CorsError {
+    #[error("invalid origin {0:?}, expected scheme://host[:port]")]
+    InvalidOrigin(String),
+    #[error("credentials cannot be allowed for any origin (*)")]
+    CredentialsWithWildcard,
+}
+
+/// Origins allowed to read responses cross-origin.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum AllowedOrigins {
+    /// No cross-origin access
+    #[default]
+    None,
+    /// Any origin (`*`)
+    Any,
+    /// The listed origins, lowercase
+    List(Vec<String>),
+}
+
+/// CORS configuration of the router.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorsPolicy {
+    pub origins: AllowedOrigins,
+    /// Whether requests may include cookies and HTTP authentication
+    pub allow_credentials: bool,
+}
+
+impl FromStr for CorsPolicy {
+    type Err = CorsError;
+
+    /// Parses a comma-separated list of origins, or `*` for any origin.
+    fn from_str(list: &str) -> Result<Self, Self::Err> {
+        let origins: Vec<&str> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let origins = match origins.as_slice() {
+            [] => AllowedOrigins::None,
+            ["*"] => AllowedOrigins::Any,
+            origins => AllowedOrigins::List(
+                origins
+                    .iter()
+                    .map(|origin| parse_origin(origin))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+
+        Ok(CorsPolicy {
+            origins,
+            allow_credentials: false,
+        })
+    }
+}
+
+/// Validates a serialized origin, returning it in lowercase.
+fn parse_origin(origin: &str) -> Result<String, CorsError> {
+    let invalid = || CorsError::InvalidOrigin(origin.to_string());
+    let (scheme, host) = origin.split_once("://").ok_or_else(invalid)?;
+    let valid_scheme = scheme
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    let valid_host = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-.:[]".contains(c));
+    if !valid_scheme || !valid_host {
+        return Err(invalid());
+    }
+    Ok(origin.to_ascii_lowercase())
+}
+
+impl CorsPolicy {
+    /// Allows or forbids credentialed requests.
+    ///
+    /// Fails if any origin is allowed, which browsers reject for credentialed
+    /// requests.
+    pub fn with_credentials(mut self, allow: bool) -> Result<Self, CorsError> {
+        if allow && self.origins == AllowedOrigins::Any {
+            return Err(CorsError::CredentialsWithWildcard);
+        }
+        self.allow_credentials = allow;
+        Ok(self)
+    }
+
+    /// Returns whether an origin may read responses.
+    pub fn allows(&self, origin: &str) -> bool {
+        match &self.origins {
+            AllowedOrigins::None => false,
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => origins.iter().any(|o| o.eq_ignore_ascii_case(origin)),
+        }
+    }
+
+    /// Builds the layer applying the policy, or `None` if cross-origin
+    /// access is disabled.
+    pub fn layer(&self) -> Option<CorsLayer> {
+        let allow_origin = match &self.origins {
+            AllowedOrigins::None => return None,
+            AllowedOrigins::Any => AllowOrigin::any(),
+            AllowedOrigins::List(origins) => AllowOrigin::list(
+                origins
+                    .iter()
+                    .map(|origin| HeaderValue::from_str(origin).unwrap()),
+            ),
+        };
+
+        // Wildcard request headers are not allowed with credentials
+        let allow_headers = if self.allow_credentials {
+            AllowHeaders::mirror_request()
+        } else {
+            AllowHeaders::any()
+        };
+
+        Some(
+            CorsLayer::new()
+                .allow_origin(allow_origin)
+                .allow_methods([Method::GET, Method::HEAD])
+                .allow_headers(allow_headers)
+                .allow_credentials(self.allow_credentials)
+                .expose_headers([header::CONTENT_LANGUAGE, header::VARY])
+                .max_age(PREFLIGHT_MAX_AGE),
+        )
+    }
+}
+
+/// Returns whether a JSONP callback name is safe to embed in a script.
+///
+/// Accepts JavaScript identifiers and dotted paths of identifiers, such as
+/// `handle` or `jQuery123.cb`, excluding reserved words.
+pub fn is_valid_callback(callback: &str) -> bool {
+    callback.len() <= MAX_CALLBACK_LEN
+        && CALLBACK.is_match(callback)
+        && !callback
+            .split('.')
+            .any(|part| RESERVED_WORDS.contains(&part))
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    callback: Option<String>,
+}
+
+/// Middleware wrapping JSON responses in a JSONP callback.
+///
+/// Requests with a `callback` query parameter are negotiated as JSON, and
+/// successful JSON responses are returned as `application/javascript`
+/// calling the named function. Invalid callback names are rejected with
+/// `400 Bad Request`.
+pub async fn jsonp(mut request: Request, next: Next) -> Response {
+    let callback = Query::<CallbackQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|query| query.0.callback);
+    let Some(callback) = callback else {
+        return next.run(request).await;
+    };
+    if !is_valid_callback(&callback) {
+        return (StatusCode::BAD_REQUEST, "Invalid JSONP callback name\n").into_response();
+    }
+
+    // Script tags send `Accept: */*`
+    request
+        .extensions_mut()
+        .insert(AcceptOverride(HeaderValue::from_static("application/json")));
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(json) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The comment guards against content sniffing of the first bytes
+    let mut script = format!("/**/ {callback}(").into_bytes();
+    script.extend_from_slice(&json);
+    script.extend_from_slice(b");\n");
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/javascript; charset=utf-8"),
+    );
+    parts.headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    Response::from_parts(parts, Body::from(script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            "".parse::<CorsPolicy>().unwrap().origins,
+            AllowedOrigins::None
+        );
+        assert_eq!(
+            " * ".parse::<CorsPolicy>().unwrap().origins,
+            AllowedOrigins::Any
+        );
+        assert_eq!(
+            "HTTPS://Example.com,http://localhost:3000"
+                .parse::<CorsPolicy>()
+                .unwrap()
+                .origins,
+            AllowedOrigins::List(vec![
+                "https://example.com".to_string(),
+                "http://localhost:3000".to_string()
+            ])
+        );
+        assert_eq!(
+            "example.com".parse::<CorsPolicy>(),
+            Err(CorsError::InvalidOrigin("example.com".to_string()))
+        );
+        assert!("https://example.com/path".parse::<CorsPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_credentials_with_wildcard() {
+        let any: CorsPolicy = "*".parse().unwrap();
+        assert_eq!(
+            any.with_credentials(true),
+            Err(CorsError::CredentialsWithWildcard)
+        );
+
+        let list: CorsPolicy = "https://example.com".parse().unwrap();
+        assert!(list.with_credentials(true).unwrap().allow_credentials);
+    }
+
+    #[test]
+    fn test_disabled_without_origins() {
+        assert!(CorsPolicy::default().layer().is_none());
+    }
+
+    #[test]
+    fn test_callback_validation() {
+        assert!(is_valid_callback("cb"));
+        assert!(is_valid_callback("$.fn_1"));
+        assert!(!is_valid_callback(""));
+        assert!(!is_valid_callback("1cb"));
+        assert!(!is_valid_callback("a..b"));
+        assert!(!is_valid_callback("cb();evil"));
+        assert!(!is_valid_callback("café"));
+        assert!(!is_valid_callback("window.delete"));
+        assert!(!is_valid_callback(&"a".repeat(MAX_CALLBACK_LEN + 1)));
+    }
+}
@@ -0,0 +1,239 @@
+//! Server-sent events: what the server sees of a connection, pushed
+//! periodically.
+//!
+//! `GET /events` with `Accept: text/event-stream` opens a stream sending a
+//! `connection` event every [`Config::events_interval`]. An address cannot
+//! change during a connection: when the network changes, the connection
+//! breaks and `EventSource` reconnects, sending the last event ID. The ID of
+//! every event is the client's address, so the first event of the new
+//! connection is a `change` event when the address differs.
+//!
+//! ```text
+//! event: change
+//! id: 2001:db8::1
+//! data: {"ip":"2001:db8::1","version":6,...,"previous_ip":"192.0.2.1",...}
+//! retry: 3000
+//! ```
+//!
+//! With `Accept: application/json`, responds with a single snapshot instead.
+//!
+//! [`Config::events_interval`]: crate::config::Config::events_interval
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Version},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures_util::stream;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::content_negotiation::Negotiator;
+use crate::handle_index::{ip_version, real_ip, real_port};
+use crate::negotiated::{Negotiated, Representations};
+
+/// How long `EventSource` waits before reconnecting.
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+/// Shortest time between two events, whatever the configuration.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The representations of `/events`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventsFormat {
+    /// A `text/event-stream` of updates
+    Stream,
+    /// A single JSON snapshot
+    Json,
+}
+
+lazy_static! {
+    static ref NEGOTIATOR: Negotiator<EventsFormat> = Negotiator::new()
+        .offer("text/event-stream", EventsFormat::Stream)
+        .offer("application/json", EventsFormat::Json);
+}
+
+impl Representations for EventsFormat {
+    fn negotiator() -> &'static Negotiator<Self> {
+        &NEGOTIATOR
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sse" | "stream" => Some(EventsFormat::Stream),
+            "json" => Some(EventsFormat::Json),
+            _ => None,
+        }
+    }
+
+    fn names() -> Vec<&'static str> {
+        vec!["sse", "json"]
+    }
+}
+
+/// What the server sees of a connection.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ConnectionInfo {
+    pub ip: String,
+    /// 4 or 6, or `null` if the address is not an IP address
+    pub version: Option<u8>,
+    /// The client's source port
+    pub port: u16,
+    pub http_version: String,
+    /// The address of the previous connection, if it was different
+    pub previous_ip: Option<String>,
+    /// Number of events sent before this one on the connection
+    pub sequence: u64,
+    /// Seconds since the connection was opened
+    pub connected_for: u64,
+}
+
+impl ConnectionInfo {
+    /// Returns the event kind: `change` if the address differs from the
+    /// previous connection, `connection` otherwise.
+    pub fn kind(&self) -> &'static str {
+        if self.previous_ip.is_some() {
+            "change"
+        } else {
+            "connection"
+        }
+    }
+}
+
+/// Returns the address of the previous connection from `Last-Event-ID`, if
+/// it differs from `ip`.
+fn previous_ip(headers: &HeaderMap, ip: &str) -> Option<String> {
+    headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|previous| !previous.is_empty() && *previous != ip)
+        .map(str::to_string)
+}
+
+/// Builds the event of an update.
+fn event(info: &ConnectionInfo) -> Event {
+    let event = Event::default()
+        .event(info.kind())
+        .id(info.ip.clone())
+        .data(serde_json::to_string(info).unwrap_or_default());
+    if info.sequence == 0 {
+        event.retry(RECONNECT_DELAY)
+    } else {
+        event
+    }
+}
+
+/// Streams the client's address and connection details
+///
+/// Sends a `connection` event periodically, or a `change` event when the
+/// address differs from the `Last-Event-ID` sent on reconnection.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    responses(
+        (status = 200, description = "Updates on the connection", content(
+            (String = "text/event-stream"),
+            (ConnectionInfo = "application/json"),
+        )),
+        (status = 406, description = "No acceptable format"),
+    )
+)]
+pub async fn handle_events(
+    State(config): State<Arc<Config>>,
+    Negotiated(format, _): Negotiated<EventsFormat>,
+    headers: HeaderMap,
+    version: Version,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Response {
+    let ip = real_ip(&headers, addr.ip());
+    let info = ConnectionInfo {
+        version: ip_version(&ip),
+        port: real_port(&headers, addr.port()),
+        http_version: format!("{version:?}"),
+        previous_ip: previous_ip(&headers, &ip),
+        sequence: 0,
+        connected_for: 0,
+        ip,
+    };
+
+    if format == EventsFormat::Json {
+        return Json(info).into_response();
+    }
+
+    let opened = Instant::now();
+    let mut interval = tokio::time::interval(config.events_interval.max(MIN_INTERVAL));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let updates = stream::unfold(
+        (info, interval),
+        move |(mut info, mut interval)| async move {
+            interval.tick().await;
+            info.connected_for = opened.elapsed().as_secs();
+            let event = event(&info);
+
+            // The change is only reported once
+            info.previous_ip = None;
+            info.sequence += 1;
+            Some((Ok::<_, Infallible>(event), (info, interval)))
+        },
+    );
+
+    let mut response = Sse::new(updates).into_response();
+    // Keep reverse proxies such as nginx from buffering the stream
+    response.headers_mut().insert(
+        HeaderName::from_static("x-accel-buffering"),
+        HeaderValue::from_static("no"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(previous_ip: Option<&str>) -> ConnectionInfo {
+        ConnectionInfo {
+            ip: "2001:db8::1".to_string(),
+            version: Some(6),
+            port: 4711,
+            http_version: "HTTP/1.1".to_string(),
+            previous_ip: previous_ip.map(str::to_string),
+            sequence: 0,
+            connected_for: 0,
+        }
+    }
+
+    #[test]
+    fn test_previous_ip_only_when_changed() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(previous_ip(&headers, "192.0.2.1"), None);
+
+        headers.insert("last-event-id", HeaderValue::from_static("192.0.2.1"));
+        assert_eq!(previous_ip(&headers, "192.0.2.1"), None);
+        assert_eq!(
+            previous_ip(&headers, "2001:db8::1"),
+            Some("192.0.2.1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_event_kind() {
+        assert_eq!(info(None).kind(), "connection");
+        assert_eq!(info(Some("192.0.2.1")).kind(), "change");
+    }
+}
@@ -1,47 +1,66 @@
 use axum::{
-    http::{HeaderMap, StatusCode},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::IntoResponse,
 };
 use base64::{engine::general_purpose, Engine};
 use lazy_static::lazy_static;
 use shake::digest::{ExtendableOutput, Update, XofReader};
 use shake::Shake128;
-use std::include_str;
 
-const MAIN_CSS: &str = include_str!("main.css");
+use crate::compression::{precompressed, Encoding, Precompressed};
+use crate::negotiated::Vary;
+
+const MAIN_CSS: Precompressed = precompressed!("main.css");
 
 lazy_static! {
-    static ref CSS_ETAG: String = compute_etag(MAIN_CSS);
+    static ref CSS_ETAG: String = compute_etag(MAIN_CSS.identity);
 }
 
-fn compute_etag(data: &str) -> String {
+fn compute_etag(data: &[u8]) -> String {
     let mut hasher = Shake128::default();
-    hasher.update(data.as_bytes());
+    hasher.update(data);
 
     let mut reader = hasher.finalize_xof();
     let mut result = [0u8; 16];
     reader.read(&mut result);
 
-    format!(
-        "W/\"{hash}\"",
-        hash = general_purpose::STANDARD_NO_PAD.encode(result)
-    )
+    general_purpose::STANDARD_NO_PAD.encode(result)
+}
+
+/// Returns the ETag of the CSS in the given encoding.
+///
+/// Each encoding is a different representation, so it gets its own ETag.
+fn etag(encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Identity => format!("W/\"{}\"", CSS_ETAG.as_str()),
+        _ => format!("W/\"{}-{}\"", CSS_ETAG.as_str(), encoding.as_str()),
+    }
 }
 
-pub async fn axum_handle_css(headers: HeaderMap) -> impl IntoResponse {
+pub async fn axum_handle_css(vary: Vary, headers: HeaderMap) -> impl IntoResponse {
+    vary.add(header::ACCEPT_ENCODING);
+    let encoding = Encoding::negotiate(&headers);
+    let etag = etag(encoding);
+
     let etag_matches = headers
         .get("if-none-match")
-        .map(|v| v == CSS_ETAG.as_str())
+        .map(|v| v == etag.as_str())
         .unwrap_or(false);
 
     let mut headers = HeaderMap::new();
     headers.insert("Content-Type", "text/css; charset=utf-8".parse().unwrap());
     headers.insert("Cache-Control", "max-age=86400".parse().unwrap());
-    headers.insert("ETag", CSS_ETAG.as_str().parse().unwrap());
+    headers.insert("ETag", etag.parse().unwrap());
+    if encoding != Encoding::Identity {
+        headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        );
+    }
 
     if etag_matches {
         (StatusCode::NOT_MODIFIED, headers).into_response()
     } else {
-        (headers, MAIN_CSS).into_response()
+        (headers, MAIN_CSS.get(encoding)).into_response()
     }
 }
@@ -0,0 +1,315 @@
+use axum::extract::{Path, State};
+use axum::http::{HeaderName, StatusCode, Version};
+use axum::{extract::ConnectInfo, http::HeaderMap, response::IntoResponse, Json};
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::config::Config;
+use crate::content_negotiation::Negotiator;
+use crate::handle_index::real_port;
+use crate::negotiated::{Negotiated, Representations, Vary};
+
+#[derive(Debug, Clone, Copy)]
+pub enum FieldFormat {
+    Plain,
+    Json,
+}
+
+lazy_static! {
+    static ref NEGOTIATOR: Negotiator<FieldFormat> = Negotiator::new()
+        .offer("text/plain", FieldFormat::Plain)
+        .offer("application/json", FieldFormat::Json);
+}
+
+impl Representations for FieldFormat {
+    fn negotiator() -> &'static Negotiator<Self> {
+        &NEGOTIATOR
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "txt" | "text" | "plain" => Some(FieldFormat::Plain),
+            "json" => Some(FieldFormat::Json),
+            _ => None,
+        }
+    }
+
+    fn names() -> Vec<&'static str> {
+        vec!["txt", "json"]
+    }
+}
+
+/// Responds with a single value, as plain text or as a single-field JSON object.
+///
+/// The representation is selected by the [`Negotiated`] extractor.
+fn field_response(format: FieldFormat, key: &str, value: Value) -> axum::response::Response {
+    if let FieldFormat::Json = format {
+        let mut body = Map::new();
+        body.insert(key.to_string(), value);
+        return Json(body).into_response();
+    }
+
+    let text = match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    };
+    format!("{}\n", text).into_response()
+}
+
+/// Responds with the displayed value of a request header.
+///
+/// The response depends on that header, so it is recorded in `vary` as well.
+fn header_response(
+    config: &Config,
+    format: FieldFormat,
+    vary: &Vary,
+    headers: &HeaderMap,
+    name: &str,
+) -> axum::response::Response {
+    let Ok(name) = HeaderName::try_from(name.to_ascii_lowercase()) else {
+        return (StatusCode::BAD_REQUEST, "invalid header name\n").into_response();
+    };
+    vary.add(name.clone());
+
+    let value = headers
+        .get(&name)
+        .and_then(|v| config.header_policy.display_value(name.as_str(), v));
+
+    match value {
+        Some(value) => field_response(format, name.as_str(), Value::String(value)),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("header {name} not present\n"),
+        )
+            .into_response(),
+    }
+}
+
+/// Shows the value of a request header
+#[utoipa::path(
+    get,
+    path = "/headers/{name}",
+    tag = "fields",
+    params(("name" = String, Path, description = "Header name, case-insensitive")),
+    responses(
+        (status = 200, description = "The value", content(
+            (String = "text/plain"),
+            (Object = "application/json"),
+        )),
+        (status = 400, description = "Invalid header name"),
+        (status = 404, description = "Header not present or hidden"),
+        (status = 406, description = "No acceptable format"),
+    )
+)]
+pub async fn handle_header(
+    State(config): State<Arc<Config>>,
+    Negotiated(format, _): Negotiated<FieldFormat>,
+    vary: Vary,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    header_response(&config, format, &vary, &headers, &name)
+}
+
+/// Shows the `User-Agent` header
+#[utoipa::path(
+    get,
+    path = "/user-agent",
+    tag = "fields",
+    responses(
+        (status = 200, description = "The value", content(
+            (String = "text/plain"),
+            (Object = "application/json"),
+        )),
+        (status = 404, description = "Header not present or hidden"),
+        (status = 406, description = "No acceptable format"),
+    )
+)]
+pub async fn handle_user_agent(
+    State(config): State<Arc<Config>>,
+    Negotiated(format, _): Negotiated<FieldFormat>,
+    vary: Vary,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    header_response(&config, format, &vary, &headers, "user-agent")
+}
+
+/// Shows the `Accept-Language` header
+#[utoipa::path(
+    get,
+    path = "/accept-language",
+    tag = "fields",
+    responses(
+        (status = 200, description = "The value", content(
+            (String = "text/plain"),
+            (Object = "application/json"),
+        )),
+        (status = 404, description = "Header not present or hidden"),
+        (status = 406, description = "No acceptable format"),
+    )
+)]
+pub async fn handle_accept_language(
+    State(config): State<Arc<Config>>,
+    Negotiated(format, _): Negotiated<FieldFormat>,
+    vary: Vary,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    header_response(&config, format, &vary, &headers, "accept-language")
+}
+
+/// Shows the client's source port
+///
+/// Behind a reverse proxy, the port is read from `X-Real-Port` like the
+/// address is from `X-Real-IP`; without it, this is the port of the TCP peer.
+#[utoipa::path(
+    get,
+    path = "/port",
+    tag = "fields",
+    responses(
+        (status = 200, description = "The value", content(
+            (String = "text/plain"),
+            (Object = "application/json"),
+        )),
+        (status = 406, description = "No acceptable format"),
+    )
+)]
+pub async fn handle_port(
+    Negotiated(format, _): Negotiated<FieldFormat>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let port = real_port(&headers, addr.port());
+    field_response(format, "port", Value::from(port))
+}
+
+/// Shows the HTTP version of the request
+#[utoipa::path(
+    get,
+    path = "/http-version",
+    tag = "fields",
+    responses(
+        (status = 200, description = "The value", content(
+            (String = "text/plain"),
+            (Object = "application/json"),
+        )),
+        (status = 406, description = "No acceptable format"),
+    )
+)]
+pub async fn handle_http_version(
+    Negotiated(format, _): Negotiated<FieldFormat>,
+    version: Version,
+) -> impl IntoResponse {
+    let version = Value::from(format!("{version:?}"));
+    field_response(format, "http-version", version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header_policy::{parse_patterns, RedactionMode};
+    use axum::{body::Body, extract::Request, http::header, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app(config: Config) -> Router {
+        Router::new()
+            .route("/headers/{name}", get(handle_header))
+            .route("/user-agent", get(handle_user_agent))
+            .route("/accept-language", get(handle_accept_language))
+            .route("/port", get(handle_port))
+            .route("/http-version", get(handle_http_version))
+            .with_state(Arc::new(config))
+    }
+
+    async fn get_field(
+        config: Config,
+        uri: &str,
+        headers: &[(&'static str, &'static str)],
+    ) -> (StatusCode, Option<String>, String) {
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4711))));
+
+        let response = app(config).oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_plain_and_json() {
+        let headers = [("user-agent", "curl/8.0"), ("accept", "text/plain")];
+        let (status, content_type, body) =
+            get_field(Config::default(), "/user-agent", &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.unwrap().starts_with("text/plain"));
+        assert_eq!(body, "curl/8.0\n");
+
+        let headers = [("user-agent", "curl/8.0"), ("accept", "application/json")];
+        let (status, content_type, body) =
+            get_field(Config::default(), "/user-agent", &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert_eq!(body, r#"{"user-agent":"curl/8.0"}"#);
+
+        let headers = [("accept", "application/json")];
+        let (_, _, body) = get_field(Config::default(), "/port", &headers).await;
+        assert_eq!(body, r#"{"port":4711}"#);
+
+        let (_, _, body) = get_field(Config::default(), "/http-version", &[]).await;
+        assert_eq!(body, "HTTP/1.1\n");
+    }
+
+    #[tokio::test]
+    async fn test_not_acceptable() {
+        let headers = [("accept-language", "de"), ("accept", "image/png")];
+        let (status, _, _) = get_field(Config::default(), "/accept-language", &headers).await;
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[tokio::test]
+    async fn test_header_by_name() {
+        let headers = [("x-custom", "value")];
+        let (status, _, body) = get_field(Config::default(), "/headers/X-Custom", &headers).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "value\n"));
+
+        let (status, _, _) = get_field(Config::default(), "/headers/bad%20name", &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = get_field(Config::default(), "/headers/x-missing", &headers).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_hidden_header_not_found() {
+        let mut config = Config::default();
+        config.header_policy.redact = parse_patterns("x-custom");
+        config.header_policy.mode = RedactionMode::Hide;
+
+        let headers = [("x-custom", "value")];
+        let (status, _, _) = get_field(config, "/headers/x-custom", &headers).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_port_behind_proxy() {
+        let headers = [("x-real-port", "51234")];
+        let (_, _, body) = get_field(Config::default(), "/port", &headers).await;
+        assert_eq!(body, "51234\n");
+    }
+}
@@ -1,79 +1,179 @@
 use askama::Template;
-use axum::http::StatusCode;
-use axum::{extract::ConnectInfo, http::HeaderMap, response::IntoResponse, Json};
-use serde::Serialize;
-use std::{collections::BTreeMap, net::SocketAddr};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderValue};
+use axum::{extract::ConnectInfo, http::HeaderMap, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::content_negotiation::{parse_accept, MediaType};
+use crate::api_v1;
+use crate::binary;
+use crate::client_hints::{self, ClientHints};
+use crate::config::Config;
+use crate::header_policy::HeaderPolicy;
+use crate::i18n::{self, Locale};
+use crate::negotiated::{Format, Negotiated, PlainText, Rendered, SelectedBy, Vary, PRECEDENCE};
+use crate::prometheus::{Exposition, MetricFamily, Metrics, Sample};
+use crate::structured_fields::{self, Field};
+use crate::user_agent::UserAgentInfo;
 
-#[derive(Debug, Serialize, Template)]
+#[derive(Debug, Serialize, Template, ToSchema)]
 #[template(path = "index.html")]
 pub struct IpResponse {
+    /// The client address, as raw bytes in binary formats
+    #[serde(serialize_with = "binary::ip_address")]
     pub ip: String,
     pub headers: std::collections::BTreeMap<String, String>,
+    pub user_agent: Option<UserAgentInfo>,
+    pub client_hints: Option<ClientHints>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub structured_headers: BTreeMap<String, Field>,
+    /// How the response format was chosen
+    pub meta: Meta,
+    /// The locale the HTML page is rendered in
+    #[serde(skip)]
+    pub locale: &'static Locale,
 }
 
-pub async fn handle_index(
-    headers: HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-) -> impl IntoResponse {
-    let accept_header = headers
-        .get("Accept")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("*/*");
-    let directives = parse_accept(accept_header);
+/// Describes how the representation of an [`IpResponse`] was selected.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Meta {
+    /// The name of the format, usable as `?format=` or URL suffix
+    #[schema(value_type = String)]
+    pub format: &'static str,
+    pub selected_by: SelectedBy,
+    /// The sources of the format, in order of precedence
+    #[schema(value_type = Vec<String>)]
+    pub precedence: &'static [&'static str],
+}
 
-    let json_mt: MediaType = "application/json".try_into().unwrap();
-    let html_mt: MediaType = "text/html".try_into().unwrap();
-    let plain_mt: MediaType = "text/plain".try_into().unwrap();
+impl Meta {
+    pub fn new(format: Format, selected_by: SelectedBy) -> Self {
+        Meta {
+            format: format.name(),
+            selected_by,
+            precedence: PRECEDENCE,
+        }
+    }
+}
 
-    let ip = real_ip(&headers, addr.ip());
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IndexQuery {
+    /// Language range overriding `Accept-Language`
+    pub lang: Option<String>,
+    /// Name of the format overriding `Accept`, read by [`Negotiated`]
+    pub format: Option<String>,
+}
 
-    for d in directives {
-        if plain_mt.matches(&d.media_type) {
-            return handle_index_plain(ip).into_response();
-        } else if html_mt.matches(&d.media_type) {
-            return handle_index_html(headers, ip).into_response();
-        } else if json_mt.matches(&d.media_type) {
-            return handle_index_json(headers, ip).into_response();
+impl IpResponse {
+    pub fn new(config: &Config, headers: &HeaderMap, ip: String, meta: Meta) -> Self {
+        IpResponse {
+            ip,
+            headers: used_headers_axum(headers, &config.header_policy),
+            user_agent: parse_user_agent(config, headers),
+            client_hints: ClientHints::from_headers(headers, |name| {
+                config.header_policy.reveals_value(name)
+            }),
+            structured_headers: parse_structured_headers(config, headers),
+            meta,
+            locale: i18n::default_locale(),
         }
     }
-
-    handle_index_plain(ip).into_response()
 }
 
-pub fn handle_index_plain(ip: String) -> impl IntoResponse {
-    format!("{}\n", ip).into_response()
+impl PlainText for IpResponse {
+    fn plain_text(&self) -> String {
+        self.ip.clone()
+    }
 }
 
-fn handle_index_html(headers: HeaderMap, ip: String) -> impl IntoResponse {
-    let template = IpResponse {
-        ip,
-        headers: used_headers_axum(&headers),
-    };
+impl Metrics for IpResponse {
+    /// Exposes the address, its IP version and its autonomous system.
+    ///
+    /// Every sample has the same labels, empty when unknown, so the series
+    /// does not change when a value appears. There is no ASN database yet,
+    /// so `asn` is always empty.
+    fn metrics(&self) -> Vec<MetricFamily> {
+        let version = ip_version(&self.ip).map(|v| v.to_string());
+        let labels = vec![
+            ("ip", self.ip.clone()),
+            ("version", version.unwrap_or_default()),
+            ("asn", String::new()),
+        ];
 
-    match template.render() {
-        Ok(html) => {
-            let mut response_headers = HeaderMap::new();
-            response_headers.insert("Content-Type", "text/html; charset=utf-8".parse().unwrap());
-            (response_headers, html).into_response()
-        }
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Template rendering failed",
-        )
-            .into_response(),
+        vec![MetricFamily {
+            name: "ip_info_client",
+            help: "The client's IP address as seen by the server.",
+            samples: vec![Sample::info(labels)],
+        }]
     }
 }
 
-pub fn handle_index_json(headers: HeaderMap, ip: String) -> impl IntoResponse {
-    let headers = used_headers_axum(&headers);
-    let response_body = IpResponse { ip, headers };
+/// Shows the client's IP address and request details
+///
+/// The format is chosen by URL suffix, the `format` query parameter or the
+/// `Accept` header, in that order.
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "ip",
+    params(IndexQuery),
+    responses(
+        (status = 200, description = "The client's IP address and request details", body = IpResponse),
+        (status = 400, description = "Unknown format"),
+        (status = 406, description = "No acceptable format"),
+    )
+)]
+pub async fn handle_index(
+    State(config): State<Arc<Config>>,
+    Negotiated(format, selected_by): Negotiated<Format>,
+    vary: Vary,
+    Query(query): Query<IndexQuery>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let ip = real_ip(&headers, addr.ip());
+    let mut ip_response = IpResponse::new(&config, &headers, ip, Meta::new(format, selected_by));
+    // Only the HTML page is translated
+    if format == Format::Html {
+        ip_response.locale = i18n::select(query.lang.as_deref(), &headers, &vary);
+    }
+    // The user agent and client hints are shown in every format but the
+    // address-only ones
+    if !matches!(format, Format::Plain | Format::Prometheus) {
+        vary.add(header::USER_AGENT);
+        for name in client_hints::HEADER_NAMES.iter() {
+            vary.add(name.clone());
+        }
+    }
+    let locale = ip_response.locale;
+    let mut response = match format {
+        Format::Prometheus => Exposition(ip_response).into_response(),
+        _ => Rendered(format, ip_response).into_response(),
+    };
 
-    let mut response_headers = HeaderMap::new();
-    response_headers.insert("Content-Type", "application/json".parse().unwrap());
+    if format == Format::Html && response.status().is_success() {
+        // Ask Chromium-based browsers for the high-entropy hints, retrying
+        // the request if the critical ones were not sent.
+        let response_headers = response.headers_mut();
+        response_headers.insert("Accept-CH", client_hints::ACCEPT_CH.clone());
+        response_headers.insert("Critical-CH", client_hints::CRITICAL_CH.clone());
+        response_headers.insert(
+            header::CONTENT_LANGUAGE,
+            HeaderValue::from_static(locale.tag),
+        );
+    }
+
+    if format == Format::Json && response.status().is_success() {
+        api_v1::deprecate_unversioned(response.headers_mut());
+    }
 
-    (response_headers, Json(response_body))
+    response
 }
 
 fn format_ip(ip: std::net::IpAddr) -> String {
@@ -116,10 +216,96 @@ pub fn real_ip(headers: &HeaderMap, conn_ip: std::net::IpAddr) -> String {
     }
 }
 
-fn used_headers_axum(headers: &HeaderMap) -> BTreeMap<String, String> {
+/// Returns the client's source port: `X-Real-Port` when set by the reverse
+/// proxy that sets `X-Real-IP`, otherwise the port of the TCP peer.
+///
+/// `X-Forwarded-Port` is not used, as proxies set it to the port they
+/// listen on rather than the client's.
+pub fn real_port(headers: &HeaderMap, conn_port: u16) -> u16 {
+    headers
+        .get("x-real-port")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|port| port.split(',').next()?.trim().parse().ok())
+        .unwrap_or(conn_port)
+}
+
+/// Returns the IP version of an address given as text, or `None` if it is
+/// not a valid address.
+pub(crate) fn ip_version(ip: &str) -> Option<u8> {
+    match ip.parse::<IpAddr>().ok()? {
+        IpAddr::V4(_) => Some(4),
+        IpAddr::V6(_) => Some(6),
+    }
+}
+
+pub(crate) fn used_headers_axum(
+    headers: &HeaderMap,
+    policy: &HeaderPolicy,
+) -> BTreeMap<String, String> {
+    policy.apply(headers)
+}
+
+pub(crate) fn parse_user_agent(config: &Config, headers: &HeaderMap) -> Option<UserAgentInfo> {
+    if !config.header_policy.reveals_value("user-agent") {
+        return None;
+    }
+
+    headers
+        .get("user-agent")
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| config.user_agent_parser.parse(ua))
+}
+
+/// Parses the visible request headers that are known structured fields.
+///
+/// Headers that fail to parse are left out.
+pub(crate) fn parse_structured_headers(
+    config: &Config,
+    headers: &HeaderMap,
+) -> BTreeMap<String, Field> {
     headers
         .iter()
-        .filter(|(k, _)| k.as_str() != "x-real-ip" && !k.as_str().starts_with("x-forwarded-"))
-        .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
+        .filter(|(k, _)| config.header_policy.reveals_value(k.as_str()))
+        .filter_map(|(k, v)| {
+            let field_type = structured_fields::known_field_type(k.as_str())?;
+            let field = structured_fields::parse(field_type, v.to_str().ok()?).ok()?;
+            Some((k.to_string(), field))
+        })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prometheus;
+
+    fn metrics(ip: &str) -> String {
+        let meta = Meta::new(Format::Prometheus, SelectedBy::Path);
+        let response = IpResponse::new(&Config::default(), &HeaderMap::new(), ip.to_string(), meta);
+        prometheus::to_text(&response.metrics())
+    }
+
+    #[test]
+    fn test_real_port() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(real_port(&headers, 4711), 4711);
+
+        headers.insert("x-forwarded-port", HeaderValue::from_static("443"));
+        assert_eq!(real_port(&headers, 4711), 4711);
+
+        headers.insert("x-real-port", HeaderValue::from_static("51234"));
+        assert_eq!(real_port(&headers, 4711), 51234);
+
+        headers.insert("x-real-port", HeaderValue::from_static("nope"));
+        assert_eq!(real_port(&headers, 4711), 4711);
+    }
+
+    #[test]
+    fn test_metrics_keep_their_labels() {
+        assert!(metrics("2001:db8::1")
+            .contains("ip_info_client{ip=\"2001:db8::1\",version=\"6\",asn=\"\"} 1\n"));
+        assert!(
+            metrics("unknown").contains("ip_info_client{ip=\"unknown\",version=\"\",asn=\"\"} 1\n")
+        );
+    }
+}
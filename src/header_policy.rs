@@ -0,0 +1,405 @@
+//! Policy deciding which request headers are echoed back to the client.
+//!
+//! Deployments behind corporate proxies often receive headers carrying
+//! credentials (`Authorization`, `Cookie`, injected `X-Auth-*` tokens). A
+//! [`HeaderPolicy`] filters headers through an allowlist and a denylist and
+//! redacts sensitive values before they are displayed.
+//!
+//! Header patterns are matched case-insensitively against the header name and
+//! may contain the glob wildcards `*` (any sequence) and `?` (any character).
+//!
+//! # Example
+//!
+//! ```
+//! use axum::http::HeaderMap;
+//! use ip_info::header_policy::{HeaderPolicy, RedactionMode};
+//!
+//! let mut headers = HeaderMap::new();
+//! headers.insert("user-agent", "curl/8.0".parse().unwrap());
+//! headers.insert("authorization", "Bearer secret".parse().unwrap());
+//! headers.insert("x-forwarded-for", "10.0.0.1".parse().unwrap());
+//!
+//! let policy = HeaderPolicy::default().with_redaction_mode(RedactionMode::Hide);
+//! let visible = policy.apply(&headers);
+//!
+//! assert_eq!(visible.get("user-agent").map(String::as_str), Some("curl/8.0"));
+//! assert!(!visible.contains_key("authorization"));
+//! assert!(!visible.contains_key("x-forwarded-for"));
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write, str::FromStr};
+
+use axum::http::{HeaderMap, HeaderValue};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Headers that are never displayed unless the denylist is overridden.
+///
+/// These are set by the reverse proxy and are already reflected in the
+/// reported IP address and port.
+pub const DEFAULT_DENIED: &[&str] = &["x-real-ip", "x-real-port", "x-forwarded-*"];
+
+/// Headers whose values are redacted unless the redaction list is overridden.
+pub const DEFAULT_REDACTED: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-*",
+    "x-csrf-token",
+    "x-xsrf-token",
+    "x-amz-security-token",
+    "cf-access-jwt-assertion",
+];
+
+/// The value shown for headers redacted by [`RedactionMode::Mask`].
+pub const MASK: &str = "****";
+
+/// Number of hex digits of the SHA-256 digest shown by [`RedactionMode::Hash`].
+const HASH_PREFIX_LEN: usize = 16;
+
+/// Errors that can occur when building a header policy from configuration.
+#[derive(Error, Debug, PartialEq)]
+pub enum PolicyError {
+    /// The redaction mode is not one of `hide`, `mask` or `hash`
+    #[error("invalid redaction mode: {0}")]
+    InvalidRedactionMode(String),
+}
+
+/// How the value of a redacted header is displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedactionMode {
+    /// The header is omitted entirely
+    Hide,
+    /// The header is shown with its value replaced by [`MASK`], which does
+    /// not reveal the length of the value
+    #[default]
+    Mask,
+    /// The header is shown as a truncated SHA-256 digest of its value, so
+    /// equal values can be recognised without revealing them
+    Hash,
+}
+
+impl FromStr for RedactionMode {
+    type Err = PolicyError;
+
+    fn from_str(s: &str) -> Result<Self, PolicyError> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "hide" => Ok(RedactionMode::Hide),
+            "mask" => Ok(RedactionMode::Mask),
+            "hash" => Ok(RedactionMode::Hash),
+            _ => Err(PolicyError::InvalidRedactionMode(s.to_string())),
+        }
+    }
+}
+
+/// A header name pattern, either an exact name or a glob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderPattern(String);
+
+impl HeaderPattern {
+    /// Creates a pattern, normalising it to lower case.
+    pub fn new(pattern: &str) -> Self {
+        HeaderPattern(pattern.trim().to_ascii_lowercase())
+    }
+
+    /// Checks whether a (lower case) header name matches this pattern.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ip_info::header_policy::HeaderPattern;
+    ///
+    /// assert!(HeaderPattern::new("X-Auth-*").matches("x-auth-user"));
+    /// assert!(HeaderPattern::new("cookie").matches("cookie"));
+    /// assert!(!HeaderPattern::new("cookie").matches("set-cookie"));
+    /// ```
+    pub fn matches(&self, name: &str) -> bool {
+        glob_match(self.0.as_bytes(), name.as_bytes())
+    }
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    // Iterative matcher with single-star backtracking.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Parses a comma-separated list of header patterns.
+///
+/// Empty entries are skipped, so an empty string yields an empty list.
+pub fn parse_patterns(list: &str) -> Vec<HeaderPattern> {
+    list.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(HeaderPattern::new)
+        .collect()
+}
+
+/// Decides which request headers are displayed and how their values are shown.
+///
+/// A header is displayed if it matches the allowlist (or the allowlist is
+/// empty) and does not match the denylist. Displayed headers matching the
+/// redaction list have their value replaced according to the
+/// [`RedactionMode`].
+#[derive(Debug, Clone)]
+pub struct HeaderPolicy {
+    /// If non-empty, only headers matching one of these patterns are shown
+    pub allow: Vec<HeaderPattern>,
+    /// Headers matching one of these patterns are never shown
+    pub deny: Vec<HeaderPattern>,
+    /// Headers matching one of these patterns have their value redacted
+    pub redact: Vec<HeaderPattern>,
+    /// How redacted values are displayed
+    pub mode: RedactionMode,
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        HeaderPolicy {
+            allow: Vec::new(),
+            deny: DEFAULT_DENIED
+                .iter()
+                .map(|p| HeaderPattern::new(p))
+                .collect(),
+            redact: DEFAULT_REDACTED
+                .iter()
+                .map(|p| HeaderPattern::new(p))
+                .collect(),
+            mode: RedactionMode::default(),
+        }
+    }
+}
+
+impl HeaderPolicy {
+    /// Returns the policy with the given redaction mode.
+    pub fn with_redaction_mode(mut self, mode: RedactionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Checks whether a header is displayed at all.
+    pub fn is_visible(&self, name: &str) -> bool {
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|p| p.matches(name));
+        let denied = self.deny.iter().any(|p| p.matches(name));
+
+        allowed && !denied
+    }
+
+    /// Checks whether a header is displayed with its value unredacted.
+    pub fn reveals_value(&self, name: &str) -> bool {
+        self.is_visible(name) && !self.redact.iter().any(|p| p.matches(name))
+    }
+
+    /// Returns the value to display for a header, or `None` if it is hidden.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axum::http::HeaderValue;
+    /// use ip_info::header_policy::HeaderPolicy;
+    ///
+    /// let policy = HeaderPolicy::default();
+    /// let value = HeaderValue::from_static("secret");
+    ///
+    /// assert_eq!(policy.display_value("cookie", &value).as_deref(), Some("****"));
+    /// assert_eq!(policy.display_value("accept", &value).as_deref(), Some("secret"));
+    /// assert_eq!(policy.display_value("x-real-ip", &value), None);
+    /// ```
+    pub fn display_value(&self, name: &str, value: &HeaderValue) -> Option<String> {
+        if !self.is_visible(name) {
+            return None;
+        }
+
+        let value = String::from_utf8_lossy(value.as_bytes());
+        if self.reveals_value(name) {
+            return Some(value.into_owned());
+        }
+
+        match self.mode {
+            RedactionMode::Hide => None,
+            RedactionMode::Mask => Some(MASK.to_string()),
+            RedactionMode::Hash => Some(hash_value(&value)),
+        }
+    }
+
+    /// Applies the policy to a set of request headers.
+    ///
+    /// Repeated headers are collapsed to their last value.
+    pub fn apply(&self, headers: &HeaderMap) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .filter_map(|(k, v)| {
+                self.display_value(k.as_str(), v)
+                    .map(|v| (k.to_string(), v))
+            })
+            .collect()
+    }
+}
+
+fn hash_value(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    let mut hex = String::with_capacity(HASH_PREFIX_LEN);
+    for byte in digest.iter().take(HASH_PREFIX_LEN / 2) {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    format!("sha256:{hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (k, v) in pairs {
+            map.insert(*k, HeaderValue::from_static(v));
+        }
+        map
+    }
+
+    #[test]
+    fn test_glob_exact() {
+        assert!(HeaderPattern::new("accept").matches("accept"));
+        assert!(!HeaderPattern::new("accept").matches("accept-language"));
+    }
+
+    #[test]
+    fn test_glob_star() {
+        let pattern = HeaderPattern::new("x-*-token");
+        assert!(pattern.matches("x-csrf-token"));
+        assert!(pattern.matches("x--token"));
+        assert!(!pattern.matches("x-csrf-tokens"));
+    }
+
+    #[test]
+    fn test_glob_question_mark() {
+        let pattern = HeaderPattern::new("x-?");
+        assert!(pattern.matches("x-a"));
+        assert!(!pattern.matches("x-ab"));
+    }
+
+    #[test]
+    fn test_glob_only_star() {
+        assert!(HeaderPattern::new("*").matches("anything"));
+        assert!(HeaderPattern::new("*").matches(""));
+    }
+
+    #[test]
+    fn test_pattern_case_insensitive() {
+        assert!(HeaderPattern::new("X-Auth-*").matches("x-auth-token"));
+    }
+
+    #[test]
+    fn test_parse_patterns() {
+        let patterns = parse_patterns("cookie, X-Auth-*,,");
+        assert_eq!(
+            patterns,
+            vec![HeaderPattern::new("cookie"), HeaderPattern::new("x-auth-*")]
+        );
+        assert!(parse_patterns("").is_empty());
+    }
+
+    #[test]
+    fn test_redaction_mode_from_str() {
+        assert_eq!("hide".parse(), Ok(RedactionMode::Hide));
+        assert_eq!("MASK".parse(), Ok(RedactionMode::Mask));
+        assert_eq!(" hash ".parse(), Ok(RedactionMode::Hash));
+        assert_eq!(
+            "drop".parse::<RedactionMode>(),
+            Err(PolicyError::InvalidRedactionMode("drop".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_default_policy_hides_proxy_headers() {
+        let visible = HeaderPolicy::default().apply(&headers(&[
+            ("x-real-ip", "1.2.3.4"),
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-forwarded-proto", "https"),
+            ("accept", "*/*"),
+        ]));
+        assert_eq!(visible.keys().collect::<Vec<_>>(), vec!["accept"]);
+    }
+
+    #[test]
+    fn test_default_policy_masks_sensitive_headers() {
+        let visible = HeaderPolicy::default().apply(&headers(&[
+            ("authorization", "Basic abc"),
+            ("x-auth-user", "alice"),
+        ]));
+        // The mask does not reveal the length of the values
+        assert_eq!(visible["authorization"], MASK);
+        assert_eq!(visible["x-auth-user"], MASK);
+    }
+
+    #[test]
+    fn test_hide_mode() {
+        let policy = HeaderPolicy::default().with_redaction_mode(RedactionMode::Hide);
+        let visible = policy.apply(&headers(&[("cookie", "a=b"), ("accept", "*/*")]));
+        assert!(!visible.contains_key("cookie"));
+        assert!(visible.contains_key("accept"));
+    }
+
+    #[test]
+    fn test_hash_mode() {
+        let policy = HeaderPolicy::default().with_redaction_mode(RedactionMode::Hash);
+        let visible = policy.apply(&headers(&[("cookie", "abc")]));
+        // SHA-256("abc") = ba7816bf8f01cfea...
+        assert_eq!(visible["cookie"], "sha256:ba7816bf8f01cfea");
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let policy = HeaderPolicy {
+            allow: parse_patterns("accept*"),
+            ..HeaderPolicy::default()
+        };
+        let visible = policy.apply(&headers(&[
+            ("accept", "*/*"),
+            ("accept-language", "en"),
+            ("user-agent", "curl"),
+        ]));
+        assert_eq!(
+            visible.keys().collect::<Vec<_>>(),
+            vec!["accept", "accept-language"]
+        );
+    }
+
+    #[test]
+    fn test_denylist_wins_over_allowlist() {
+        let policy = HeaderPolicy {
+            allow: parse_patterns("*"),
+            deny: parse_patterns("user-agent"),
+            ..HeaderPolicy::default()
+        };
+        let visible = policy.apply(&headers(&[("accept", "*/*"), ("user-agent", "curl")]));
+        assert_eq!(visible.keys().collect::<Vec<_>>(), vec!["accept"]);
+    }
+
+    #[test]
+    fn test_non_utf8_value_does_not_panic() {
+        let mut map = HeaderMap::new();
+        map.insert("x-custom", HeaderValue::from_bytes(b"caf\xe9").unwrap());
+        let visible = HeaderPolicy::default().apply(&map);
+        assert_eq!(visible["x-custom"], "caf\u{fffd}");
+    }
+}
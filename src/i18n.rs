@@ -0,0 +1,188 @@
+//! Translations of the HTML page.
+//!
+//! Each locale has a message catalog in `src/locales/<tag>.yaml`, embedded at
+//! compile time. The locale of a request is taken from the `lang` query
+//! parameter if it names an available locale, and negotiated from the
+//! `Accept-Language` header otherwise, falling back to English.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::i18n;
+//!
+//! let locale = i18n::negotiate("de-CH, de;q=0.9, en;q=0.8").unwrap();
+//! assert_eq!(locale.tag, "de");
+//! assert_eq!(locale.messages.your_ip_is, "deine ip ist:");
+//!
+//! // The `lang` override accepts extended language ranges
+//! assert_eq!(i18n::find("pt-*").unwrap().tag, "pt-BR");
+//! ```
+
+use axum::http::{header, HeaderMap};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::content_negotiation::{extended_filter, negotiate_language, parse_accept_language};
+use crate::negotiated::Vary;
+use crate::user_agent::DeviceType;
+
+/// The built-in catalogs; the first one is the default.
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.yaml")),
+    ("de", include_str!("locales/de.yaml")),
+    ("es", include_str!("locales/es.yaml")),
+    ("fr", include_str!("locales/fr.yaml")),
+    ("pt-BR", include_str!("locales/pt-BR.yaml")),
+];
+
+/// The translated strings of the HTML page.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Messages {
+    pub title: String,
+    pub your_ip_is: String,
+    pub copy_to_clipboard: String,
+    /// Joins browser and operating system, as in "Firefox on Linux"
+    pub on: String,
+    pub bot: String,
+    pub client_hints: String,
+    pub brands: String,
+    pub full_version_list: String,
+    pub platform: String,
+    pub mobile: String,
+    pub model: String,
+    pub architecture: String,
+    pub form_factors: String,
+    /// Label of the round-trip time measured over the WebSocket
+    pub latency: String,
+    pub device_types: DeviceTypes,
+}
+
+/// The translated names of the [`DeviceType`]s.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceTypes {
+    pub desktop: String,
+    pub mobile: String,
+    pub tablet: String,
+    pub bot: String,
+    pub other: String,
+}
+
+impl Messages {
+    /// Returns the translated name of a device type.
+    pub fn device_type(&self, kind: &DeviceType) -> &str {
+        let names = &self.device_types;
+        match kind {
+            DeviceType::Desktop => &names.desktop,
+            DeviceType::Mobile => &names.mobile,
+            DeviceType::Tablet => &names.tablet,
+            DeviceType::Bot => &names.bot,
+            DeviceType::Other => &names.other,
+        }
+    }
+}
+
+/// A locale the HTML page is available in.
+#[derive(Debug)]
+pub struct Locale {
+    /// The language tag, as used in `Content-Language`
+    pub tag: &'static str,
+    pub messages: Messages,
+}
+
+lazy_static! {
+    static ref LOCALES: Vec<Locale> = CATALOGS
+        .iter()
+        .map(|(tag, yaml)| Locale {
+            tag,
+            messages: serde_yaml::from_str(yaml)
+                .unwrap_or_else(|e| panic!("invalid built-in catalog {tag}: {e}")),
+        })
+        .collect();
+    static ref TAGS: Vec<&'static str> = LOCALES.iter().map(|l| l.tag).collect();
+}
+
+/// Returns the available locales, the default first.
+pub fn locales() -> &'static [Locale] {
+    &LOCALES
+}
+
+/// Returns the locale used when no available locale is acceptable.
+pub fn default_locale() -> &'static Locale {
+    &LOCALES[0]
+}
+
+/// Returns the first locale matching an extended language range, such as
+/// `de` or `*-BR`.
+pub fn find(range: &str) -> Option<&'static Locale> {
+    LOCALES.iter().find(|l| extended_filter(range, l.tag))
+}
+
+/// Returns the available locale preferred by an `Accept-Language` header
+/// value, or `None` if none is acceptable.
+pub fn negotiate(accept_language: &str) -> Option<&'static Locale> {
+    let ranges = parse_accept_language(accept_language);
+    negotiate_language(&ranges, &TAGS).map(|i| &LOCALES[i])
+}
+
+/// Selects the locale of a request.
+///
+/// `lang` is the value of the `lang` query parameter. If it does not name an
+/// available locale, the `Accept-Language` header is negotiated and recorded
+/// in `vary`.
+pub fn select(lang: Option<&str>, headers: &HeaderMap, vary: &Vary) -> &'static Locale {
+    if let Some(locale) = lang.and_then(find) {
+        return locale;
+    }
+
+    vary.add(header::ACCEPT_LANGUAGE);
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(negotiate)
+        .unwrap_or_else(default_locale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_catalogs_parse() {
+        assert_eq!(locales().len(), CATALOGS.len());
+        assert_eq!(default_locale().tag, "en");
+    }
+
+    #[test]
+    fn test_negotiate_region() {
+        assert_eq!(negotiate("pt-BR").unwrap().tag, "pt-BR");
+        assert_eq!(negotiate("pt-PT").unwrap().tag, "pt-BR");
+        assert_eq!(negotiate("de-AT").unwrap().tag, "de");
+        assert!(negotiate("pt-PT, pt;q=0").is_none());
+        assert_eq!(negotiate("fr-CA, fr;q=0.5").unwrap().tag, "fr");
+    }
+
+    #[test]
+    fn test_select_override() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, "de".parse().unwrap());
+
+        let vary = Vary::default();
+        assert_eq!(select(Some("ES"), &headers, &vary).tag, "es");
+        assert!(vary.names().is_empty());
+
+        assert_eq!(select(Some("xx"), &headers, &vary).tag, "de");
+        assert_eq!(vary.names(), vec![header::ACCEPT_LANGUAGE]);
+    }
+
+    #[test]
+    fn test_select_default() {
+        let vary = Vary::default();
+        assert_eq!(select(None, &HeaderMap::new(), &vary).tag, "en");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, "ja".parse().unwrap());
+        assert_eq!(select(None, &headers, &vary).tag, "en");
+    }
+}
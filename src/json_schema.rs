@@ -0,0 +1,296 @@
+//! JSON Schemas of the JSON responses.
+//!
+//! Each schema of the OpenAPI document is also published as a standalone
+//! JSON Schema (draft 2020-12) at `/schemas/{name}.json`, for validators that
+//! do not read OpenAPI. Schemas it references are included under `$defs`.
+//!
+//! The [`described_by`] middleware links successful JSON responses to their
+//! schema with a `Link: </schemas/IpResponse.json>; rel="describedby"`
+//! header. The schema of a route is the one documented for its
+//! `application/json` response.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::json_schema::to_json_schema;
+//! use serde_json::json;
+//!
+//! let components = json!({
+//!     "Pair": {
+//!         "type": "array",
+//!         "items": {"$ref": "#/components/schemas/Item"}
+//!     },
+//!     "Item": {"type": "string"},
+//! });
+//!
+//! assert_eq!(
+//!     to_json_schema(components.as_object().unwrap(), "Pair").unwrap(),
+//!     json!({
+//!         "$schema": "https://json-schema.org/draft/2020-12/schema",
+//!         "$id": "/schemas/Pair.json",
+//!         "title": "Pair",
+//!         "type": "array",
+//!         "items": {"$ref": "#/$defs/Item"},
+//!         "$defs": {"Item": {"type": "string"}},
+//!     })
+//! );
+//! ```
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+    routing::get,
+    Router,
+};
+use serde_json::{Map, Value};
+use utoipa::openapi::{OpenApi, RefOr};
+
+/// The JSON Schema dialect of the published schemas.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Media type of the published schemas.
+pub const SCHEMA_MEDIA_TYPE: &str = "application/schema+json";
+
+/// Prefix of references to OpenAPI components.
+const COMPONENT_REF: &str = "#/components/schemas/";
+
+/// Returns the URL path of a published schema.
+pub fn schema_path(name: &str) -> String {
+    format!("/schemas/{name}.json")
+}
+
+/// Builds a standalone JSON Schema from the OpenAPI component schemas.
+///
+/// Returns `None` if there is no component with that name.
+pub fn to_json_schema(components: &Map<String, Value>, name: &str) -> Option<Value> {
+    let mut root = components.get(name)?.clone();
+
+    // Collect the referenced components, transitively
+    let mut defs = Map::new();
+    let mut pending = references(&root);
+    while let Some(reference) = pending.pop() {
+        if reference == name || defs.contains_key(&reference) {
+            continue;
+        }
+        let Some(schema) = components.get(&reference) else {
+            continue;
+        };
+        pending.extend(references(schema));
+        defs.insert(reference, schema.clone());
+    }
+
+    rewrite_references(&mut root, name);
+    for schema in defs.values_mut() {
+        rewrite_references(schema, name);
+    }
+
+    let mut document = Map::new();
+    document.insert("$schema".to_string(), DIALECT.into());
+    document.insert("$id".to_string(), schema_path(name).into());
+    document.insert("title".to_string(), name.into());
+    if let Value::Object(schema) = root {
+        document.extend(schema);
+    }
+    if !defs.is_empty() {
+        defs.sort_keys();
+        document.insert("$defs".to_string(), Value::Object(defs));
+    }
+    Some(Value::Object(document))
+}
+
+/// Returns the names of the components referenced by a schema.
+fn references(schema: &Value) -> Vec<String> {
+    match schema {
+        Value::Object(entries) => entries
+            .iter()
+            .flat_map(|(key, value)| match (key.as_str(), value) {
+                ("$ref", Value::String(reference)) => reference
+                    .strip_prefix(COMPONENT_REF)
+                    .map(str::to_string)
+                    .into_iter()
+                    .collect(),
+                _ => references(value),
+            })
+            .collect(),
+        Value::Array(items) => items.iter().flat_map(references).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Points component references into `$defs`, or to the document itself for
+/// the root schema.
+fn rewrite_references(schema: &mut Value, root: &str) {
+    match schema {
+        Value::Object(entries) => {
+            for (key, value) in entries.iter_mut() {
+                match (key.as_str(), value) {
+                    ("$ref", Value::String(reference)) => {
+                        if let Some(name) = reference.strip_prefix(COMPONENT_REF) {
+                            *reference = if name == root {
+                                "#".to_string()
+                            } else {
+                                format!("#/$defs/{name}")
+                            };
+                        }
+                    }
+                    (_, value) => rewrite_references(value, root),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rewrite_references(item, root);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The published schemas and the routes they describe.
+#[derive(Debug, Default)]
+pub struct Schemas {
+    /// Serialized schemas by name
+    documents: Vec<(String, String)>,
+    /// `Link` header values by route path
+    links: HashMap<String, HeaderValue>,
+}
+
+impl Schemas {
+    pub fn new(api: &OpenApi) -> Self {
+        let components = api
+            .components
+            .as_ref()
+            .and_then(|components| serde_json::to_value(&components.schemas).ok())
+            .and_then(|schemas| match schemas {
+                Value::Object(schemas) => Some(schemas),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let documents = components
+            .keys()
+            .filter_map(|name| {
+                let schema = to_json_schema(&components, name)?;
+                Some((name.clone(), serde_json::to_string_pretty(&schema).ok()?))
+            })
+            .collect();
+
+        let mut links = HashMap::new();
+        for (path, item) in &api.paths.paths {
+            // Templated paths do not match request paths
+            if path.contains('{') {
+                continue;
+            }
+            let Some(RefOr::T(response)) = item
+                .get
+                .as_ref()
+                .and_then(|operation| operation.responses.responses.get("200"))
+            else {
+                continue;
+            };
+            let Some(RefOr::Ref(reference)) = response
+                .content
+                .get("application/json")
+                .and_then(|content| content.schema.as_ref())
+            else {
+                continue;
+            };
+            let Some(name) = reference.ref_location.strip_prefix(COMPONENT_REF) else {
+                continue;
+            };
+            let link = format!("<{}>; rel=\"describedby\"", schema_path(name));
+            if let Ok(link) = HeaderValue::from_str(&link) {
+                links.insert(path.clone(), link);
+            }
+        }
+
+        Schemas { documents, links }
+    }
+
+    /// Returns the `Link` header value for responses of a route.
+    pub fn link(&self, path: &str) -> Option<&HeaderValue> {
+        self.links.get(path)
+    }
+
+    /// Returns the routes serving the schemas.
+    pub fn router<S>(&self) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let mut router = Router::new();
+        for (name, document) in &self.documents {
+            let document = document.clone();
+            router = router.route(
+                &schema_path(name),
+                get(move || async move { ([(header::CONTENT_TYPE, SCHEMA_MEDIA_TYPE)], document) }),
+            );
+        }
+        router
+    }
+}
+
+/// Middleware linking successful JSON responses to their schema.
+pub async fn described_by(
+    State(schemas): State<Arc<Schemas>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let link = schemas.link(request.uri().path()).cloned();
+    let mut response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if let Some(link) = link.filter(|_| response.status().is_success() && is_json) {
+        response.headers_mut().append(header::LINK, link);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_recursive_reference_points_to_root() {
+        let components = json!({
+            "Node": {"properties": {"next": {"$ref": "#/components/schemas/Node"}}},
+        });
+        let schema = to_json_schema(components.as_object().unwrap(), "Node").unwrap();
+        assert_eq!(schema["properties"]["next"]["$ref"], "#");
+        assert!(schema.get("$defs").is_none());
+    }
+
+    #[test]
+    fn test_transitive_references() {
+        let components = json!({
+            "A": {"$ref": "#/components/schemas/B"},
+            "B": {"items": {"$ref": "#/components/schemas/C"}},
+            "C": {"type": "string"},
+            "D": {"type": "null"},
+        });
+        let schema = to_json_schema(components.as_object().unwrap(), "A").unwrap();
+        assert_eq!(schema["$ref"], "#/$defs/B");
+        assert_eq!(schema["$defs"]["B"]["items"]["$ref"], "#/$defs/C");
+        assert_eq!(
+            schema["$defs"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            ["B", "C"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_schema() {
+        assert_eq!(to_json_schema(&Map::new(), "Missing"), None);
+    }
+}
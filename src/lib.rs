@@ -1,3 +1,23 @@
+pub mod api_v1;
+pub mod binary;
+pub mod client_hints;
+pub mod compression;
+pub mod config;
 pub mod content_negotiation;
+pub mod cors;
+pub mod events;
 pub mod handle_css;
+pub mod handle_fields;
 pub mod handle_index;
+pub mod header_policy;
+pub mod i18n;
+pub mod json_schema;
+pub mod negotiated;
+pub mod openapi;
+pub mod prometheus;
+pub mod shell;
+pub mod structured_fields;
+pub mod transcode;
+pub mod user_agent;
+pub mod websocket;
+pub mod xml;
@@ -1,4 +1,4 @@
-use std::{env, net::SocketAddr};
+use std::{env, net::SocketAddr, sync::Arc};
 
 use anyhow::Result;
 use axum::{
@@ -6,11 +6,26 @@ use axum::{
     middleware::{self, Next},
     response::Response,
     routing::get,
-    Router,
+    Extension,
+};
+use ip_info::{
+    api_v1,
+    compression::compress,
+    config::Config,
+    cors::jsonp,
+    events,
+    handle_css::axum_handle_css,
+    handle_fields, handle_index,
+    json_schema::{described_by, Schemas},
+    negotiated::{track_vary, Format, NegotiationOptions},
+    openapi::{self, ApiDoc},
+    transcode::transcode,
+    websocket,
 };
-use ip_info::{handle_css::axum_handle_css, handle_index::handle_index};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use utoipa::OpenApi;
+use utoipa_axum::{router::OpenApiRouter, routes};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -24,10 +39,64 @@ async fn main() -> Result<()> {
         .and_then(|port| port.parse().ok())
         .unwrap_or(8000);
 
-    let app = Router::new()
+    let config = Config::from_env()?;
+    let negotiation = NegotiationOptions {
+        lenient: config.lenient_negotiation,
+    };
+
+    // Every documented route is registered here, so the OpenAPI document
+    // cannot drift from the router
+    let mut api = OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(openapi::index(routes!(handle_index::handle_index), None));
+    // `/ip.json`, `/ip.txt`, ... always respond in the named format
+    for format in Format::ALL {
+        api = api.routes(openapi::index(
+            routes!(handle_index::handle_index),
+            Some(format),
+        ));
+    }
+    let (app, api) = api
+        .routes(openapi::fixed_format(
+            routes!(handle_index::handle_index),
+            Format::Prometheus,
+            "/metrics/client",
+        ))
+        .routes(routes!(handle_fields::handle_header))
+        .routes(routes!(handle_fields::handle_user_agent))
+        .routes(routes!(handle_fields::handle_accept_language))
+        .routes(routes!(handle_fields::handle_port))
+        .routes(routes!(handle_fields::handle_http_version))
+        .routes(routes!(api_v1::handle_ip))
+        .routes(routes!(api_v1::handle_headers))
+        .routes(routes!(api_v1::handle_user_agent))
+        .routes(routes!(events::handle_events))
+        .routes(routes!(websocket::handle_ws))
+        .split_for_parts();
+
+    let schemas = Arc::new(Schemas::new(&api));
+
+    let app = app
+        .merge(openapi::router(&api))
+        .merge(schemas.router())
         .route("/main.css", get(axum_handle_css))
-        .route("/", get(handle_index))
+        .layer(middleware::from_fn_with_state(schemas, described_by))
+        .layer(middleware::from_fn(transcode));
+    let app = if config.jsonp {
+        app.layer(middleware::from_fn(jsonp))
+    } else {
+        app
+    };
+    let app = app.layer(middleware::from_fn(compress));
+    let app = match config.cors.layer() {
+        Some(cors) => app.layer(cors),
+        None => app,
+    };
+
+    let app = app
+        .layer(Extension(negotiation))
+        .layer(middleware::from_fn(track_vary))
         .layer(middleware::from_fn(log))
+        .with_state(Arc::new(config))
         .into_make_service_with_connect_info::<SocketAddr>();
 
     let bind_addr = format!("[::]:{port}");
@@ -0,0 +1,733 @@
+//! Glue between [`content_negotiation`](crate::content_negotiation) and axum.
+//!
+//! - [`Negotiated`] - Extractor resolving the representation the client prefers,
+//!   from the URL, the `format` query parameter or the `Accept` header
+//! - [`Rendered`] - Response rendering a value in a negotiated [`Format`]
+//! - [`Vary`] / [`track_vary()`] - Record the request headers negotiation
+//!   depended on and list them in the `Vary` response header
+//!
+//! # Example
+//!
+//! ```
+//! use askama::Template;
+//! use axum::{routing::get, Router};
+//! use ip_info::negotiated::{track_vary, Format, Negotiated, PlainText, Rendered};
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize, Template)]
+//! #[template(source = "<p>{{ greeting }}</p>", ext = "html")]
+//! struct Hello {
+//!     greeting: String,
+//! }
+//!
+//! impl PlainText for Hello {
+//!     fn plain_text(&self) -> String {
+//!         self.greeting.clone()
+//!     }
+//! }
+//!
+//! async fn hello(Negotiated(format, _): Negotiated<Format>) -> Rendered<Hello> {
+//!     Rendered(format, Hello { greeting: "hello".to_string() })
+//! }
+//!
+//! let app: Router = Router::new()
+//!     .route("/", get(hello))
+//!     .layer(axum::middleware::from_fn(track_vary));
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use askama::Template;
+use axum::{
+    extract::{FromRequestParts, Query, Request},
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::content_negotiation::{parse_accept, Negotiator, Offer};
+use crate::prometheus;
+use crate::{binary, shell, xml};
+
+/// The request headers the response depends on.
+///
+/// Cloning shares the underlying set, so handlers can record headers through
+/// the extractor while [`track_vary`] reads them afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct Vary(Arc<Mutex<Vec<HeaderName>>>);
+
+impl Vary {
+    /// Records that the response depends on a request header.
+    pub fn add(&self, name: HeaderName) {
+        let mut names = self.0.lock().unwrap();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    /// Returns the recorded header names in the order they were added.
+    pub fn names(&self) -> Vec<HeaderName> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Vary {
+    type Rejection = std::convert::Infallible;
+
+    /// Returns the request's set, or a detached one outside [`track_vary`].
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Vary>().cloned().unwrap_or_default())
+    }
+}
+
+/// Middleware adding a `Vary` header for every request header recorded during
+/// negotiation.
+///
+/// Names already listed in a `Vary` header set by the handler are not
+/// repeated.
+pub async fn track_vary(mut request: Request, next: Next) -> Response {
+    let vary = Vary::default();
+    request.extensions_mut().insert(vary.clone());
+
+    let mut response = next.run(request).await;
+
+    let existing: Vec<String> = response
+        .headers()
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_ascii_lowercase())
+        .collect();
+    let names = vary.names();
+    let missing: Vec<&str> = names
+        .iter()
+        .map(HeaderName::as_str)
+        .filter(|name| !existing.iter().any(|e| e == name || e == "*"))
+        .collect();
+
+    if !missing.is_empty() {
+        let value = HeaderValue::from_str(&missing.join(", ")).unwrap();
+        response.headers_mut().append(header::VARY, value);
+    }
+
+    response
+}
+
+/// Rejection used when none of the offered representations is acceptable.
+///
+/// Responds with `406 Not Acceptable` and lists the available media types.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotAcceptable {
+    /// The offered media types, in order of the server's offers
+    pub available: Vec<String>,
+}
+
+impl NotAcceptable {
+    /// Creates the rejection for the offers of a negotiator.
+    pub fn new<T>(negotiator: &Negotiator<T>) -> Self {
+        let available = negotiator
+            .offers()
+            .iter()
+            .map(|offer| {
+                let media_type = &offer.media_type;
+                format!("{}/{}", media_type.main_type, media_type.sub_type)
+            })
+            .collect();
+
+        NotAcceptable { available }
+    }
+}
+
+impl IntoResponse for NotAcceptable {
+    fn into_response(self) -> Response {
+        let mut body = String::from("406 Not Acceptable\n\nAvailable representations:\n");
+        for media_type in &self.available {
+            body.push_str(&format!("  {media_type}\n"));
+        }
+
+        (StatusCode::NOT_ACCEPTABLE, body).into_response()
+    }
+}
+
+/// Rejection used when the `format` query parameter names no representation.
+///
+/// Responds with `400 Bad Request` and lists the valid names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownFormat {
+    /// The name given in the request
+    pub name: String,
+    /// The names of the representations, see [`Representations::names()`]
+    pub valid: Vec<&'static str>,
+}
+
+impl IntoResponse for UnknownFormat {
+    fn into_response(self) -> Response {
+        let mut body = format!(
+            "400 Bad Request\n\nUnknown format: {}\n\nValid formats:\n",
+            self.name
+        );
+        for name in &self.valid {
+            body.push_str(&format!("  {name}\n"));
+        }
+
+        (StatusCode::BAD_REQUEST, body).into_response()
+    }
+}
+
+/// Rejection of the [`Negotiated`] extractor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NegotiationRejection {
+    UnknownFormat(UnknownFormat),
+    NotAcceptable(NotAcceptable),
+}
+
+impl From<UnknownFormat> for NegotiationRejection {
+    fn from(rejection: UnknownFormat) -> Self {
+        NegotiationRejection::UnknownFormat(rejection)
+    }
+}
+
+impl From<NotAcceptable> for NegotiationRejection {
+    fn from(rejection: NotAcceptable) -> Self {
+        NegotiationRejection::NotAcceptable(rejection)
+    }
+}
+
+impl IntoResponse for NegotiationRejection {
+    fn into_response(self) -> Response {
+        match self {
+            NegotiationRejection::UnknownFormat(rejection) => rejection.into_response(),
+            NegotiationRejection::NotAcceptable(rejection) => rejection.into_response(),
+        }
+    }
+}
+
+/// Negotiates the request's `Accept` header, recording it in `vary`.
+///
+/// A missing header accepts anything. If no offer is acceptable, returns
+/// [`NotAcceptable`], unless `lenient` is set, in which case the first offer
+/// is used.
+pub fn negotiate_accept<'a, T>(
+    negotiator: &'a Negotiator<T>,
+    headers: &HeaderMap,
+    vary: &Vary,
+    lenient: bool,
+) -> Result<&'a Offer<T>, NotAcceptable> {
+    vary.add(header::ACCEPT);
+
+    let accept_header = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("*/*");
+    let directives = parse_accept(accept_header);
+
+    match negotiator.negotiate(&directives) {
+        Some(offer) => Ok(offer),
+        None => negotiator
+            .offers()
+            .first()
+            .filter(|_| lenient)
+            .ok_or_else(|| NotAcceptable::new(negotiator)),
+    }
+}
+
+/// A set of representations an endpoint can produce.
+///
+/// Implemented by an enum with one variant per representation; the
+/// negotiator declares which media type selects which variant.
+pub trait Representations: Copy + Send + Sync + 'static {
+    /// Returns the negotiator offering every representation.
+    fn negotiator() -> &'static Negotiator<Self>;
+
+    /// Returns the representation with the given name, as used in the
+    /// `format` query parameter and URL suffixes.
+    ///
+    /// By default representations cannot be selected by name.
+    fn from_name(name: &str) -> Option<Self> {
+        let _ = name;
+        None
+    }
+
+    /// Returns the canonical names accepted by [`Representations::from_name`],
+    /// listed when a request names an unknown one.
+    fn names() -> Vec<&'static str> {
+        Vec::new()
+    }
+}
+
+/// Options for the [`Negotiated`] extractor, read from the request extensions.
+///
+/// Without this extension, negotiation is strict.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NegotiationOptions {
+    /// Use the first offer instead of rejecting with `406 Not Acceptable`
+    pub lenient: bool,
+}
+
+/// Request extension fixing the representation of a route, such as
+/// `/ip.json`.
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOverride<T>(pub T);
+
+/// Request extension replacing the `Accept` header in negotiation, for
+/// middleware implying a media type from the query, such as
+/// [`jsonp`](crate::cors::jsonp).
+#[derive(Debug, Clone)]
+pub struct AcceptOverride(pub HeaderValue);
+
+/// How the [`Negotiated`] extractor selected a representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectedBy {
+    /// The route's URL suffix, via [`FormatOverride`]
+    Path,
+    /// The `format` query parameter
+    Query,
+    /// Negotiation of the `Accept` header
+    Accept,
+}
+
+/// The sources of the representation, in order of precedence.
+pub const PRECEDENCE: &[&str] = &[
+    "path suffix, e.g. /ip.json",
+    "format query parameter, e.g. /?format=json",
+    "Accept header",
+];
+
+#[derive(Deserialize)]
+struct FormatQuery {
+    format: Option<String>,
+}
+
+/// Extractor resolving the representation preferred by the client.
+///
+/// The representation is taken from the first of these that is present (see
+/// [`PRECEDENCE`]):
+///
+/// 1. a [`FormatOverride`] request extension set on the route,
+/// 2. the `format` query parameter, looked up with
+///    [`Representations::from_name`], or an [`AcceptOverride`] set by
+///    middleware,
+/// 3. negotiation of the `Accept` header against
+///    [`Representations::negotiator`], recording it in the request's
+///    [`Vary`] set.
+///
+/// Rejects with [`UnknownFormat`] if the named format is unknown, and with
+/// [`NotAcceptable`] if no representation is acceptable, unless
+/// [`NegotiationOptions::lenient`] is set.
+#[derive(Debug, Clone, Copy)]
+pub struct Negotiated<T>(pub T, pub SelectedBy);
+
+impl<S, T> FromRequestParts<S> for Negotiated<T>
+where
+    S: Send + Sync,
+    T: Representations,
+{
+    type Rejection = NegotiationRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(FormatOverride(value)) = parts.extensions.get::<FormatOverride<T>>() {
+            return Ok(Negotiated(*value, SelectedBy::Path));
+        }
+
+        let options = parts
+            .extensions
+            .get::<NegotiationOptions>()
+            .copied()
+            .unwrap_or_default();
+
+        let name = Query::<FormatQuery>::try_from_uri(&parts.uri)
+            .ok()
+            .and_then(|query| query.0.format);
+        if let Some(name) = name {
+            return match T::from_name(&name) {
+                Some(value) => Ok(Negotiated(value, SelectedBy::Query)),
+                None if options.lenient => {
+                    let value = T::negotiator().offers()[0].value;
+                    Ok(Negotiated(value, SelectedBy::Query))
+                }
+                None => Err(UnknownFormat {
+                    name,
+                    valid: T::names(),
+                }
+                .into()),
+            };
+        }
+
+        if let Some(AcceptOverride(accept)) = parts.extensions.get::<AcceptOverride>() {
+            let headers = HeaderMap::from_iter([(header::ACCEPT, accept.clone())]);
+            return negotiate_accept(T::negotiator(), &headers, &Vary::default(), options.lenient)
+                .map(|offer| Negotiated(offer.value, SelectedBy::Query))
+                .map_err(Into::into);
+        }
+
+        let Ok(vary) = Vary::from_request_parts(parts, state).await;
+        negotiate_accept(T::negotiator(), &parts.headers, &vary, options.lenient)
+            .map(|offer| Negotiated(offer.value, SelectedBy::Accept))
+            .map_err(Into::into)
+    }
+}
+
+/// The formats a [`Rendered`] value can be produced in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The value's [`PlainText`] form
+    Plain,
+    /// The value's askama template
+    Html,
+    /// The value serialized as JSON
+    Json,
+    /// The value serialized as YAML
+    Yaml,
+    /// The value serialized as TOML
+    Toml,
+    /// The value serialized as XML, see [`xml`](crate::xml)
+    Xml,
+    /// The value serialized as CBOR, see [`binary`](crate::binary)
+    Cbor,
+    /// The value serialized as MessagePack, see [`binary`](crate::binary)
+    MsgPack,
+    /// The value as shell `export` statements, see [`shell`](crate::shell)
+    Sh,
+    /// The value as `.env` assignments, see [`shell`](crate::shell)
+    Env,
+    /// The value as a logfmt line, see [`shell`](crate::shell)
+    Logfmt,
+    /// The value's [`Metrics`](crate::prometheus::Metrics) in the Prometheus
+    /// text format, rendered by [`Exposition`](crate::prometheus::Exposition)
+    /// rather than [`Rendered`]
+    Prometheus,
+}
+
+impl Format {
+    /// All formats, in the order they are offered.
+    pub const ALL: [Format; 12] = [
+        Format::Plain,
+        Format::Html,
+        Format::Json,
+        Format::Yaml,
+        Format::Toml,
+        Format::Xml,
+        Format::Cbor,
+        Format::MsgPack,
+        Format::Sh,
+        Format::Env,
+        Format::Logfmt,
+        Format::Prometheus,
+    ];
+
+    /// Returns the name of the format, used as its URL suffix.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Plain => "txt",
+            Format::Html => "html",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Xml => "xml",
+            Format::Cbor => "cbor",
+            Format::MsgPack => "msgpack",
+            Format::Sh => "sh",
+            Format::Env => "env",
+            Format::Logfmt => "logfmt",
+            Format::Prometheus => "prom",
+        }
+    }
+
+    /// Returns the media type of the format.
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Format::Plain => "text/plain",
+            Format::Html => "text/html",
+            Format::Json => "application/json",
+            Format::Yaml => "application/yaml",
+            Format::Toml => "application/toml",
+            Format::Xml => "application/xml",
+            Format::Cbor => "application/cbor",
+            Format::MsgPack => "application/msgpack",
+            Format::Sh => "text/x-shellscript",
+            Format::Env => "text/x-dotenv",
+            Format::Logfmt => "text/x-logfmt",
+            Format::Prometheus => prometheus::MEDIA_TYPE,
+        }
+    }
+}
+
+lazy_static! {
+    static ref FORMATS: Negotiator<Format> = Format::ALL
+        .into_iter()
+        .fold(Negotiator::new(), |negotiator, format| {
+            negotiator.offer(format.media_type(), format)
+        })
+        // Still the type most MessagePack clients ask for
+        .offer("application/x-msgpack", Format::MsgPack);
+}
+
+impl Representations for Format {
+    fn negotiator() -> &'static Negotiator<Self> {
+        &FORMATS
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" | "plain" => Some(Format::Plain),
+            "yml" => Some(Format::Yaml),
+            "mpk" => Some(Format::MsgPack),
+            "shell" | "export" => Some(Format::Sh),
+            "dotenv" => Some(Format::Env),
+            "prometheus" | "metrics" => Some(Format::Prometheus),
+            name => Format::ALL.into_iter().find(|f| f.name() == name),
+        }
+    }
+
+    fn names() -> Vec<&'static str> {
+        Format::ALL.into_iter().map(Format::name).collect()
+    }
+}
+
+/// Values with a plain text representation.
+pub trait PlainText {
+    /// Returns the plain text form, without a trailing newline.
+    fn plain_text(&self) -> String;
+}
+
+/// Response rendering a value in the given format.
+///
+/// Values are not required to have metrics: [`Format::Prometheus`] responds
+/// with `406 Not Acceptable`. Handlers that expose metrics render it with
+/// [`Exposition`](crate::prometheus::Exposition) instead.
+#[derive(Debug)]
+pub struct Rendered<T>(pub Format, pub T);
+
+impl<T> IntoResponse for Rendered<T>
+where
+    T: Serialize + Template + PlainText,
+{
+    fn into_response(self) -> Response {
+        let Rendered(format, value) = self;
+        match format {
+            Format::Plain => format!("{}\n", value.plain_text()).into_response(),
+            Format::Html => match value.render() {
+                Ok(html) => {
+                    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response()
+                }
+                Err(_) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Template rendering failed",
+                )
+                    .into_response(),
+            },
+            Format::Json => Json(value).into_response(),
+            Format::Yaml => serialized(format, serde_yaml::to_string(&value)),
+            Format::Toml => serialized(format, toml::to_string(&value)),
+            Format::Xml => serialized(format, xml::to_string("response", &value)),
+            Format::Cbor => serialized(format, binary::to_cbor(&value)),
+            Format::MsgPack => serialized(format, binary::to_msgpack(&value)),
+            Format::Sh => serialized(format, shell::to_sh(&value)),
+            Format::Env => serialized(format, shell::to_env(&value)),
+            Format::Logfmt => serialized(format, shell::to_logfmt(&value)),
+            Format::Prometheus => NotAcceptable {
+                available: Format::ALL
+                    .into_iter()
+                    .filter(|f| *f != Format::Prometheus)
+                    .map(|f| f.media_type().to_string())
+                    .collect(),
+            }
+            .into_response(),
+        }
+    }
+}
+
+/// Responds with a serialized value, or `500 Internal Server Error` if it
+/// could not be serialized.
+fn serialized<B: IntoResponse, E>(format: Format, result: Result<B, E>) -> Response {
+    let media_type = format.media_type();
+    let content_type = if media_type.starts_with("text/") {
+        format!("{media_type}; charset=utf-8")
+    } else {
+        media_type.to_string()
+    };
+
+    match result {
+        Ok(body) => ([(header::CONTENT_TYPE, content_type)], body).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Serialization failed").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn negotiator() -> Negotiator<&'static str> {
+        Negotiator::new()
+            .offer("text/plain", "plain")
+            .offer("application/json", "json")
+    }
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_vary_deduplicates() {
+        let vary = Vary::default();
+        vary.add(header::ACCEPT);
+        vary.clone().add(header::ACCEPT);
+        vary.add(header::USER_AGENT);
+        assert_eq!(vary.names(), vec![header::ACCEPT, header::USER_AGENT]);
+    }
+
+    #[test]
+    fn test_negotiate_accept_records_vary() {
+        let vary = Vary::default();
+        let negotiator = negotiator();
+        let offer = negotiate_accept(&negotiator, &HeaderMap::new(), &vary, false).unwrap();
+        assert_eq!(offer.value, "plain");
+        assert_eq!(vary.names(), vec![header::ACCEPT]);
+    }
+
+    #[test]
+    fn test_negotiate_accept_not_acceptable() {
+        let vary = Vary::default();
+        let rejection =
+            negotiate_accept(&negotiator(), &accept("image/png"), &vary, false).unwrap_err();
+        assert_eq!(
+            rejection,
+            NotAcceptable {
+                available: vec!["text/plain".to_string(), "application/json".to_string()],
+            }
+        );
+        assert_eq!(
+            rejection.into_response().status(),
+            StatusCode::NOT_ACCEPTABLE
+        );
+    }
+
+    #[test]
+    fn test_negotiate_accept_lenient() {
+        let vary = Vary::default();
+        let negotiator = negotiator();
+        let offer = negotiate_accept(&negotiator, &accept("image/png"), &vary, true).unwrap();
+        assert_eq!(offer.value, "plain");
+    }
+
+    #[test]
+    fn test_format_from_name() {
+        assert_eq!(Format::from_name("json"), Some(Format::Json));
+        assert_eq!(Format::from_name("YML"), Some(Format::Yaml));
+        assert_eq!(Format::from_name("text"), Some(Format::Plain));
+        assert_eq!(Format::from_name("csv"), None);
+        for format in Format::ALL {
+            assert_eq!(Format::from_name(format.name()), Some(format));
+        }
+    }
+
+    #[test]
+    fn test_prometheus_needs_version_parameter() {
+        let best = |accept| FORMATS.negotiate(&parse_accept(accept)).map(|o| o.value);
+        assert_eq!(best("text/plain"), Some(Format::Plain));
+        assert_eq!(best("text/plain; version=0.0.4"), Some(Format::Prometheus));
+        assert_eq!(
+            best("application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5,*/*;q=0.1"),
+            Some(Format::Prometheus)
+        );
+    }
+
+    #[derive(Serialize, Template)]
+    #[template(source = "<p>{{ greeting }}</p>", ext = "html")]
+    struct Hello {
+        greeting: String,
+    }
+
+    impl PlainText for Hello {
+        fn plain_text(&self) -> String {
+            self.greeting.clone()
+        }
+    }
+
+    #[test]
+    fn test_rendered_without_metrics() {
+        let hello = || Hello {
+            greeting: "hello".to_string(),
+        };
+        let response = Rendered(Format::Plain, hello()).into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = Rendered(Format::Prometheus, hello()).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    async fn negotiated(request: Request) -> Result<Negotiated<Format>, NegotiationRejection> {
+        let (mut parts, _) = request.into_parts();
+        Negotiated::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_precedence() {
+        let request = |uri| {
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT, "text/html")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let Negotiated(format, selected_by) = negotiated(request("/")).await.unwrap();
+        assert_eq!((format, selected_by), (Format::Html, SelectedBy::Accept));
+
+        let Negotiated(format, selected_by) = negotiated(request("/?format=yaml")).await.unwrap();
+        assert_eq!((format, selected_by), (Format::Yaml, SelectedBy::Query));
+
+        let mut jsonp = request("/?callback=cb");
+        jsonp
+            .extensions_mut()
+            .insert(AcceptOverride(HeaderValue::from_static("application/json")));
+        let Negotiated(format, selected_by) = negotiated(jsonp).await.unwrap();
+        assert_eq!((format, selected_by), (Format::Json, SelectedBy::Query));
+
+        let mut path = request("/ip.json?format=yaml");
+        path.extensions_mut().insert(FormatOverride(Format::Json));
+        let Negotiated(format, selected_by) = negotiated(path).await.unwrap();
+        assert_eq!((format, selected_by), (Format::Json, SelectedBy::Path));
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_unknown_format() {
+        let request = || {
+            Request::builder()
+                .uri("/?format=csv")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let Err(NegotiationRejection::UnknownFormat(rejection)) = negotiated(request()).await
+        else {
+            panic!("expected an unknown format");
+        };
+        assert_eq!(rejection.name, "csv");
+        assert_eq!(rejection.valid, Format::names());
+
+        let response = rejection.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("Unknown format: csv"));
+        assert!(body.contains("  json\n"));
+
+        let mut lenient = request();
+        lenient
+            .extensions_mut()
+            .insert(NegotiationOptions { lenient: true });
+        let Negotiated(format, _) = negotiated(lenient).await.unwrap();
+        assert_eq!(format, Format::Plain);
+    }
+}
@@ -0,0 +1,322 @@
+//! OpenAPI description of the HTTP API.
+//!
+//! Handlers document themselves with `#[utoipa::path]` attributes and are
+//! registered through an [`OpenApiRouter`](utoipa_axum::router::OpenApiRouter)
+//! in `main.rs`, so a route cannot be served without appearing in the
+//! document, and response schemas are derived from the response types.
+//!
+//! [`router()`] serves the document at `/openapi.json` and renders it as a
+//! documentation page at `/docs`.
+
+use askama::Template;
+use axum::{
+    http::header,
+    response::{Html, IntoResponse},
+    routing::get,
+    Extension, Router,
+};
+use utoipa::openapi::{
+    path::{Operation, PathItem},
+    Content, OpenApi, Ref, RefOr, Required, Response, Schema,
+};
+use utoipa_axum::router::UtoipaMethodRouter;
+
+use crate::negotiated::{Format, FormatOverride};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(info(
+    title = "ip-info",
+    description = "Shows the client's IP address and what its request says about it."
+))]
+pub struct ApiDoc;
+
+/// Returns the schema of the index in a format: the [`IpResponse`] data
+/// model for serializations, a string for text.
+///
+/// [`IpResponse`]: crate::handle_index::IpResponse
+fn index_schema(format: Format) -> RefOr<Schema> {
+    match format {
+        Format::Json
+        | Format::Yaml
+        | Format::Toml
+        | Format::Xml
+        | Format::Cbor
+        | Format::MsgPack => Ref::from_schema_name("IpResponse").into(),
+        Format::Plain
+        | Format::Html
+        | Format::Sh
+        | Format::Env
+        | Format::Logfmt
+        | Format::Prometheus => {
+            utoipa::openapi::schema::Object::with_type(utoipa::openapi::Type::String).into()
+        }
+    }
+}
+
+/// Lists the given formats as the content of every successful response.
+fn set_content(operation: &mut Operation, formats: &[Format]) {
+    for (status, response) in operation.responses.responses.iter_mut() {
+        if !status.starts_with('2') {
+            continue;
+        }
+        if let RefOr::T(Response { content, .. }) = response {
+            content.clear();
+            for &format in formats {
+                content.insert(
+                    format.media_type().to_string(),
+                    Content::new(Some(index_schema(format))),
+                );
+            }
+        }
+    }
+}
+
+/// Documents the index handler's routes.
+///
+/// Without a format, documents `/` with every [`Format`] as content. With a
+/// format, moves the route to its URL suffix, such as `/ip.json`; see
+/// [`fixed_format()`].
+pub fn index<S>(routes: UtoipaMethodRouter<S>, format: Option<Format>) -> UtoipaMethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let Some(format) = format else {
+        let (schemas, mut paths, method_router) = routes;
+        for item in paths.paths.values_mut() {
+            if let Some(operation) = item.get.as_mut() {
+                set_content(operation, &Format::ALL);
+            }
+        }
+        return (schemas, paths, method_router);
+    };
+
+    fixed_format(routes, format, &format!("/ip.{}", format.name()))
+}
+
+/// Moves the index handler's route to `path`, always responding in `format`
+/// by adding a [`FormatOverride`].
+pub fn fixed_format<S>(
+    routes: UtoipaMethodRouter<S>,
+    format: Format,
+    path: &str,
+) -> UtoipaMethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let (schemas, mut paths, method_router) = routes;
+    let mut moved = utoipa::openapi::Paths::new();
+    for (_, mut item) in std::mem::take(&mut paths.paths) {
+        if let Some(operation) = item.get.as_mut() {
+            set_content(operation, &[format]);
+            // The format is fixed, so it is always known and acceptable
+            operation.responses.responses.remove("400");
+            operation.responses.responses.remove("406");
+            if let Some(parameters) = operation.parameters.as_mut() {
+                parameters.retain(|p| p.name != "format");
+            }
+            let suffix = path.trim_start_matches('/').replace(['/', '.'], "_");
+            operation.operation_id = operation
+                .operation_id
+                .as_ref()
+                .map(|id| format!("{id}_{suffix}"));
+        }
+        moved.paths.insert(path.to_string(), item);
+    }
+
+    let method_router = method_router.layer(Extension(FormatOverride(format)));
+    (schemas, moved, method_router)
+}
+
+/// One operation of the documentation page.
+struct OperationDoc {
+    method: &'static str,
+    path: String,
+    summary: String,
+    description: String,
+    parameters: Vec<ParameterDoc>,
+    responses: Vec<ResponseDoc>,
+}
+
+struct ParameterDoc {
+    name: String,
+    location: String,
+    required: bool,
+    description: String,
+}
+
+struct ResponseDoc {
+    status: String,
+    description: String,
+    media_types: Vec<String>,
+}
+
+/// The documentation page, rendered from the OpenAPI document.
+#[derive(Template)]
+#[template(path = "docs.html")]
+struct DocsPage {
+    title: String,
+    description: String,
+    operations: Vec<OperationDoc>,
+    schemas: Vec<String>,
+}
+
+/// Returns the operations of a path with their methods.
+fn operations(item: &PathItem) -> Vec<(&'static str, &Operation)> {
+    [
+        ("GET", &item.get),
+        ("HEAD", &item.head),
+        ("POST", &item.post),
+        ("PUT", &item.put),
+        ("PATCH", &item.patch),
+        ("DELETE", &item.delete),
+        ("OPTIONS", &item.options),
+    ]
+    .into_iter()
+    .filter_map(|(method, operation)| operation.as_ref().map(|operation| (method, operation)))
+    .collect()
+}
+
+impl DocsPage {
+    fn new(api: &OpenApi) -> Self {
+        let mut docs = Vec::new();
+        for (path, item) in &api.paths.paths {
+            for (method, operation) in operations(item) {
+                let parameters = operation
+                    .parameters
+                    .iter()
+                    .flatten()
+                    .map(|p| ParameterDoc {
+                        name: p.name.clone(),
+                        location: serde_json::to_value(&p.parameter_in)
+                            .ok()
+                            .and_then(|v| v.as_str().map(str::to_string))
+                            .unwrap_or_default(),
+                        required: matches!(p.required, Required::True),
+                        description: p.description.clone().unwrap_or_default(),
+                    })
+                    .collect();
+                let responses = operation
+                    .responses
+                    .responses
+                    .iter()
+                    .map(|(status, response)| match response {
+                        RefOr::T(response) => ResponseDoc {
+                            status: status.clone(),
+                            description: response.description.clone(),
+                            media_types: response.content.keys().cloned().collect(),
+                        },
+                        RefOr::Ref(reference) => ResponseDoc {
+                            status: status.clone(),
+                            description: reference.ref_location.clone(),
+                            media_types: Vec::new(),
+                        },
+                    })
+                    .collect();
+
+                docs.push(OperationDoc {
+                    method,
+                    path: path.clone(),
+                    summary: operation.summary.clone().unwrap_or_default(),
+                    description: operation.description.clone().unwrap_or_default(),
+                    parameters,
+                    responses,
+                });
+            }
+        }
+
+        DocsPage {
+            title: api.info.title.clone(),
+            description: api.info.description.clone().unwrap_or_default(),
+            operations: docs,
+            schemas: api
+                .components
+                .iter()
+                .flat_map(|c| c.schemas.keys().cloned())
+                .collect(),
+        }
+    }
+}
+
+/// Returns the routes serving the document and its documentation page.
+///
+/// Both are rendered once, from the document of the final router.
+pub fn router<S>(api: &OpenApi) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let json = api.to_pretty_json().expect("OpenAPI document serializes");
+    let html = DocsPage::new(api)
+        .render()
+        .expect("documentation page renders");
+
+    Router::new()
+        .route(
+            "/openapi.json",
+            get(move || async move { ([(header::CONTENT_TYPE, "application/json")], json) }),
+        )
+        .route(
+            "/docs",
+            get(move || async move { Html(html).into_response() }),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use utoipa::OpenApi as _;
+    use utoipa_axum::{router::OpenApiRouter, routes};
+
+    use super::*;
+    use crate::{api_v1, config::Config, handle_index};
+
+    fn document() -> OpenApi {
+        let mut router = OpenApiRouter::<Arc<Config>>::with_openapi(ApiDoc::openapi())
+            .routes(index(routes!(handle_index::handle_index), None));
+        for format in Format::ALL {
+            router = router.routes(index(routes!(handle_index::handle_index), Some(format)));
+        }
+        router
+            .routes(routes!(api_v1::handle_user_agent))
+            .into_openapi()
+    }
+
+    #[test]
+    fn test_index_lists_every_format() {
+        let api = document();
+        let content = |path: &str| -> Vec<String> {
+            let operation = api.paths.paths[path].get.as_ref().unwrap();
+            match &operation.responses.responses["200"] {
+                RefOr::T(response) => response.content.keys().cloned().collect(),
+                RefOr::Ref(_) => unreachable!(),
+            }
+        };
+
+        assert_eq!(content("/").len(), Format::ALL.len());
+        assert_eq!(content("/ip.yaml"), vec!["application/yaml"]);
+        assert!(api.components.unwrap().schemas.contains_key("IpResponse"));
+    }
+
+    #[test]
+    fn test_suffix_routes_drop_format_parameter() {
+        let api = document();
+        let parameters: Vec<String> = api.paths.paths["/ip.json"]
+            .get
+            .as_ref()
+            .unwrap()
+            .parameters
+            .iter()
+            .flatten()
+            .map(|p| p.name.clone())
+            .collect();
+        assert_eq!(parameters, vec!["lang"]);
+    }
+
+    #[test]
+    fn test_versioned_schemas_do_not_collide() {
+        let schemas = document().components.unwrap().schemas;
+        assert!(schemas.contains_key("Device"));
+        assert!(schemas.contains_key("v1.Device"));
+        assert!(schemas["Device"] != schemas["v1.Device"]);
+    }
+}
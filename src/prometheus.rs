@@ -0,0 +1,173 @@
+//! Prometheus text exposition format.
+//!
+//! Lets monitoring probes scrape what the server sees of them, for example
+//! to alert when the egress IP of a probe changes. Values are exposed as
+//! constant `1` gauges whose labels carry the information, like the
+//! `*_info` metrics of Prometheus exporters.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::prometheus::{to_text, MetricFamily, Sample};
+//!
+//! let family = MetricFamily {
+//!     name: "ip_info_client",
+//!     help: "The client as seen by the server",
+//!     samples: vec![Sample::info(vec![("ip", "192.0.2.1".to_string())])],
+//! };
+//!
+//! assert_eq!(
+//!     to_text(&[family]),
+//!     concat!(
+//!         "# HELP ip_info_client The client as seen by the server\n",
+//!         "# TYPE ip_info_client gauge\n",
+//!         "ip_info_client{ip=\"192.0.2.1\"} 1\n",
+//!     )
+//! );
+//! ```
+//!
+//! # References
+//!
+//! - [Exposition formats](https://prometheus.io/docs/instrumenting/exposition_formats/) - Text format version 0.0.4
+
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+/// Media type of the text format, without the `charset` parameter.
+pub const MEDIA_TYPE: &str = "text/plain; version=0.0.4";
+
+/// A gauge and its samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricFamily {
+    /// The metric name, matching `[a-zA-Z_:][a-zA-Z0-9_:]*`
+    pub name: &'static str,
+    pub help: &'static str,
+    pub samples: Vec<Sample>,
+}
+
+/// A sample of a metric, with labels in output order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+impl Sample {
+    /// Returns a sample with the value `1`, carrying information in its
+    /// labels.
+    pub fn info(labels: Vec<(&'static str, String)>) -> Self {
+        Sample { labels, value: 1.0 }
+    }
+}
+
+/// Values that can be exposed as metrics.
+pub trait Metrics {
+    fn metrics(&self) -> Vec<MetricFamily>;
+}
+
+/// Response exposing a value's metrics in the text format.
+#[derive(Debug)]
+pub struct Exposition<T>(pub T);
+
+impl<T: Metrics> IntoResponse for Exposition<T> {
+    fn into_response(self) -> Response {
+        (
+            [(header::CONTENT_TYPE, format!("{MEDIA_TYPE}; charset=utf-8"))],
+            to_text(&self.0.metrics()),
+        )
+            .into_response()
+    }
+}
+
+/// Writes metric families in the text format.
+pub fn to_text(families: &[MetricFamily]) -> String {
+    let mut text = String::new();
+    for family in families {
+        text.push_str(&format!(
+            "# HELP {} {}\n",
+            family.name,
+            escape(family.help, false)
+        ));
+        text.push_str(&format!("# TYPE {} gauge\n", family.name));
+        for sample in &family.samples {
+            text.push_str(family.name);
+            if !sample.labels.is_empty() {
+                let labels: Vec<String> = sample
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{name}=\"{}\"", escape(value, true)))
+                    .collect();
+                text.push_str(&format!("{{{}}}", labels.join(",")));
+            }
+            text.push_str(&format!(" {}\n", format_value(sample.value)));
+        }
+    }
+    text
+}
+
+/// Escapes backslashes and newlines, and double quotes in label values.
+fn escape(value: &str, quotes: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quotes => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Formats a sample value as Go's `strconv.ParseFloat` reads it.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_escaping() {
+        let family = MetricFamily {
+            name: "m",
+            help: "a \"b\"\\\n",
+            samples: vec![Sample::info(vec![("ua", "x \"y\"\\\nz".to_string())])],
+        };
+        assert_eq!(
+            to_text(&[family]),
+            "# HELP m a \"b\"\\\\\\n\n# TYPE m gauge\nm{ua=\"x \\\"y\\\"\\\\\\nz\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn test_sample_without_labels() {
+        let family = MetricFamily {
+            name: "m",
+            help: "h",
+            samples: vec![
+                Sample {
+                    labels: vec![],
+                    value: 0.5,
+                },
+                Sample {
+                    labels: vec![],
+                    value: f64::NEG_INFINITY,
+                },
+            ],
+        };
+        assert_eq!(
+            to_text(&[family]),
+            "# HELP m h\n# TYPE m gauge\nm 0.5\nm -Inf\n"
+        );
+    }
+}
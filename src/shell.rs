@@ -0,0 +1,209 @@
+//! Shell-friendly representations: shell exports, `.env` files and logfmt.
+//!
+//! Values are flattened into one variable per leaf of their JSON tree, in
+//! field order. The path to a leaf names the variable; array elements use
+//! their index. Nulls, empty objects and empty arrays are left out.
+//!
+//! - [`to_sh()`] - `export IP_INFO_HEADERS_ACCEPT='*/*'` lines for
+//!   `eval "$(curl ...)"`
+//! - [`to_env()`] - `HEADERS_ACCEPT="*/*"` lines for `.env` files
+//! - [`to_logfmt()`] - A single `headers.accept=*/*` logfmt line
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::shell;
+//! use serde_json::json;
+//!
+//! let value = json!({"ip": "::1", "headers": {"user-agent": "curl/8.0 (x'y)"}});
+//!
+//! assert_eq!(
+//!     shell::to_sh(&value).unwrap(),
+//!     "export IP_INFO_IP=::1\n\
+//!      export IP_INFO_HEADERS_USER_AGENT='curl/8.0 (x'\\''y)'\n"
+//! );
+//! assert_eq!(
+//!     shell::to_env(&value).unwrap(),
+//!     "IP=::1\nHEADERS_USER_AGENT=\"curl/8.0 (x'y)\"\n"
+//! );
+//! assert_eq!(
+//!     shell::to_logfmt(&value).unwrap(),
+//!     "ip=::1 headers.user-agent=\"curl/8.0 (x'y)\"\n"
+//! );
+//! ```
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Prefix of the variables exported by [`to_sh()`].
+pub const SH_PREFIX: &str = "IP_INFO_";
+
+/// Serializes a value as shell `export` statements.
+///
+/// Values are quoted for POSIX shells, so the output can be passed to `eval`.
+pub fn to_sh<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let mut sh = String::new();
+    for (path, value) in flatten(value)? {
+        sh.push_str(&format!(
+            "export {SH_PREFIX}{}={}\n",
+            variable_name(&path),
+            sh_quote(&value)
+        ));
+    }
+    Ok(sh)
+}
+
+/// Serializes a value as `.env` file assignments.
+pub fn to_env<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let mut env = String::new();
+    for (path, value) in flatten(value)? {
+        env.push_str(&format!(
+            "{}={}\n",
+            variable_name(&path),
+            double_quote(&value, "\\\"$`")
+        ));
+    }
+    Ok(env)
+}
+
+/// Serializes a value as a logfmt line.
+pub fn to_logfmt<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let pairs: Vec<String> = flatten(value)?
+        .into_iter()
+        .map(|(path, value)| format!("{}={}", path.join("."), double_quote(&value, "\\\"")))
+        .collect();
+    Ok(pairs.join(" ") + "\n")
+}
+
+/// Returns the leaves of a value's JSON tree with their paths.
+fn flatten<T: Serialize + ?Sized>(
+    value: &T,
+) -> Result<Vec<(Vec<String>, String)>, serde_json::Error> {
+    fn walk(path: &mut Vec<String>, value: &Value, leaves: &mut Vec<(Vec<String>, String)>) {
+        match value {
+            Value::Null => {}
+            Value::Bool(b) => leaves.push((path.clone(), b.to_string())),
+            Value::Number(n) => leaves.push((path.clone(), n.to_string())),
+            Value::String(s) => leaves.push((path.clone(), s.clone())),
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(i.to_string());
+                    walk(path, item, leaves);
+                    path.pop();
+                }
+            }
+            Value::Object(entries) => {
+                for (key, item) in entries {
+                    path.push(key.clone());
+                    walk(path, item, leaves);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    let mut leaves = Vec::new();
+    walk(&mut Vec::new(), &serde_json::to_value(value)?, &mut leaves);
+    Ok(leaves)
+}
+
+/// Joins a path into an upper-case variable name, replacing characters not
+/// allowed in shell names with `_`.
+fn variable_name(path: &[String]) -> String {
+    path.iter()
+        .map(|part| {
+            part.chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+                    _ => '_',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Characters that never need quoting.
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "@%+:,./_-".contains(c)
+}
+
+/// Quotes a value for POSIX shells, using single quotes unless the value
+/// only contains safe characters.
+fn sh_quote(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Wraps a value in double quotes unless it only contains safe characters,
+/// escaping the given characters with a backslash and newlines as `\n`.
+fn double_quote(value: &str, escaped: &str) -> String {
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return value.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            c if escaped.contains(c) => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_sh_quote() {
+        assert_eq!(sh_quote("192.0.2.1"), "192.0.2.1");
+        assert_eq!(sh_quote(""), "''");
+        assert_eq!(sh_quote("$(rm -rf /)"), "'$(rm -rf /)'");
+        assert_eq!(sh_quote("it's"), "'it'\\''s'");
+        assert_eq!(sh_quote("a\nb"), "'a\nb'");
+    }
+
+    #[test]
+    fn test_env_escapes_expansion() {
+        assert_eq!(
+            to_env(&json!({"x": "$HOME `id` \"q\"\n"})).unwrap(),
+            "X=\"\\$HOME \\`id\\` \\\"q\\\"\\n\"\n"
+        );
+    }
+
+    #[test]
+    fn test_flatten_arrays_and_nulls() {
+        let value = json!({"a": [true, null, 1.5], "b": null, "c": {}});
+        assert_eq!(
+            to_sh(&value).unwrap(),
+            "export IP_INFO_A_0=true\nexport IP_INFO_A_2=1.5\n"
+        );
+    }
+
+    #[test]
+    fn test_logfmt_quoting() {
+        assert_eq!(
+            to_logfmt(&json!({"k": "a=b", "e": ""})).unwrap(),
+            "k=\"a=b\" e=\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_variable_name() {
+        assert_eq!(
+            variable_name(&["headers".to_string(), "sec-ch-ua".to_string()]),
+            "HEADERS_SEC_CH_UA"
+        );
+    }
+}
@@ -0,0 +1,1109 @@
+//! Structured Field Values for HTTP as defined in RFC 8941.
+//!
+//! Many modern headers (Client Hints, `Priority`, `Signature`,
+//! `Cache-Status`, ...) are defined as Structured Fields: an Item, a List or
+//! a Dictionary of typed values with optional parameters. This module parses
+//! them into a typed tree and serializes them back to canonical form.
+//!
+//! # Overview
+//!
+//! - [`parse_item()`], [`parse_list()`], [`parse_dictionary()`] - Parse a field
+//!   value of a known top-level type
+//! - [`known_field_type()`] - Looks up the top-level type of a registered header
+//! - [`Field`] - A parsed field of any top-level type
+//!
+//! Parsed values implement [`Display`](std::fmt::Display), producing the
+//! canonical serialization, and [`Serialize`], producing a JSON tree where
+//! items are `{"value": ..., "params": {...}}` objects, tokens are
+//! `{"token": ...}` and byte sequences `{"binary": <base64>}`. The JSON
+//! Schema of that tree is given by their [`ToSchema`] implementations.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::structured_fields::{parse_dictionary, BareItem, Member};
+//!
+//! let priority = parse_dictionary("u=1, i").unwrap();
+//! assert_eq!(priority[0].0, "u");
+//! assert_eq!(priority[1].0, "i");
+//!
+//! match &priority[0].1 {
+//!     Member::Item(item) => assert_eq!(item.bare_item, BareItem::Integer(1)),
+//!     Member::InnerList(_) => unreachable!(),
+//! }
+//!
+//! // Bare keys are boolean true and serialize back without a value
+//! assert_eq!(
+//!     ip_info::structured_fields::Field::Dictionary(priority).to_string(),
+//!     "u=1, i"
+//! );
+//! ```
+//!
+//! # References
+//!
+//! - [RFC 8941](https://www.rfc-editor.org/rfc/rfc8941) - Structured Field Values for HTTP
+
+use std::{borrow::Cow, fmt};
+
+use base64::{
+    alphabet,
+    engine::{general_purpose, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use thiserror::Error;
+use utoipa::{
+    openapi::{
+        schema::{AnyOf, Array, Object, Ref, Type},
+        RefOr, Schema,
+    },
+    PartialSchema, ToSchema,
+};
+
+/// Byte sequences may omit padding when parsed (RFC 8941 Section 4.2.7).
+const BASE64_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Errors that can occur when parsing a structured field value.
+#[derive(Error, Debug, PartialEq)]
+pub enum ParseError {
+    /// The input ended where more characters were required
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    /// A character not allowed at this position was encountered
+    #[error("unexpected character {0:?} at position {1}")]
+    UnexpectedCharacter(char, usize),
+    /// An integer or decimal has too many digits or a malformed fraction
+    #[error("invalid number at position {0}")]
+    InvalidNumber(usize),
+    /// A byte sequence contains characters outside the base64 alphabet
+    #[error("invalid byte sequence at position {0}")]
+    InvalidByteSequence(usize),
+}
+
+/// The top-level type of a structured field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Item,
+    List,
+    Dictionary,
+}
+
+/// Request headers known to be structured fields, with their top-level type.
+pub const KNOWN_FIELDS: &[(&str, FieldType)] = &[
+    ("client-cert", FieldType::Item),
+    ("client-cert-chain", FieldType::List),
+    ("content-digest", FieldType::Dictionary),
+    ("priority", FieldType::Dictionary),
+    ("repr-digest", FieldType::Dictionary),
+    ("sec-ch-prefers-color-scheme", FieldType::Item),
+    ("sec-ch-prefers-reduced-motion", FieldType::Item),
+    ("sec-ch-ua", FieldType::List),
+    ("sec-ch-ua-arch", FieldType::Item),
+    ("sec-ch-ua-bitness", FieldType::Item),
+    ("sec-ch-ua-form-factors", FieldType::List),
+    ("sec-ch-ua-full-version-list", FieldType::List),
+    ("sec-ch-ua-mobile", FieldType::Item),
+    ("sec-ch-ua-model", FieldType::Item),
+    ("sec-ch-ua-platform", FieldType::Item),
+    ("sec-ch-ua-platform-version", FieldType::Item),
+    ("sec-ch-ua-wow64", FieldType::Item),
+    ("sec-fetch-dest", FieldType::Item),
+    ("sec-fetch-mode", FieldType::Item),
+    ("sec-fetch-site", FieldType::Item),
+    ("sec-fetch-user", FieldType::Item),
+    ("sec-purpose", FieldType::Item),
+    ("signature", FieldType::Dictionary),
+    ("signature-input", FieldType::Dictionary),
+    ("want-content-digest", FieldType::Dictionary),
+    ("want-repr-digest", FieldType::Dictionary),
+];
+
+/// Returns the top-level type of a known structured header.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::structured_fields::{known_field_type, FieldType};
+///
+/// assert_eq!(known_field_type("Priority"), Some(FieldType::Dictionary));
+/// assert_eq!(known_field_type("user-agent"), None);
+/// ```
+pub fn known_field_type(name: &str) -> Option<FieldType> {
+    KNOWN_FIELDS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, field_type)| *field_type)
+}
+
+/// A bare item value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BareItem {
+    /// An integer of at most 15 digits
+    Integer(i64),
+    /// A decimal with at most 12 integer and 3 fractional digits
+    Decimal(f64),
+    /// A string of printable ASCII characters
+    String(String),
+    /// A token, an unquoted identifier such as `gzip` or `*`
+    Token(String),
+    /// Binary content, transmitted as base64 between colons
+    ByteSequence(Vec<u8>),
+    /// A boolean, transmitted as `?1` or `?0`
+    Boolean(bool),
+}
+
+/// Parameters attached to an item or inner list, in order of appearance.
+pub type Parameters = Vec<(String, BareItem)>;
+
+/// A bare item with its parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub bare_item: BareItem,
+    pub params: Parameters,
+}
+
+/// A parenthesized list of items with parameters of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InnerList {
+    pub items: Vec<Item>,
+    pub params: Parameters,
+}
+
+/// A member of a List or Dictionary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Member {
+    Item(Item),
+    InnerList(InnerList),
+}
+
+/// A List field.
+pub type List = Vec<Member>;
+
+/// A Dictionary field, with keys in order of first appearance.
+pub type Dictionary = Vec<(String, Member)>;
+
+/// A parsed structured field of any top-level type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Item(Item),
+    List(List),
+    Dictionary(Dictionary),
+}
+
+/// Parses a field value of the given top-level type.
+pub fn parse(field_type: FieldType, input: &str) -> Result<Field, ParseError> {
+    match field_type {
+        FieldType::Item => parse_item(input).map(Field::Item),
+        FieldType::List => parse_list(input).map(Field::List),
+        FieldType::Dictionary => parse_dictionary(input).map(Field::Dictionary),
+    }
+}
+
+/// Parses an Item field value.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::structured_fields::{parse_item, BareItem};
+///
+/// let item = parse_item(r#""Windows""#).unwrap();
+/// assert_eq!(item.bare_item, BareItem::String("Windows".to_string()));
+///
+/// assert!(parse_item("?2").is_err());
+/// ```
+pub fn parse_item(input: &str) -> Result<Item, ParseError> {
+    Parser::new(input).top_level(Parser::item)
+}
+
+/// Parses a List field value.
+///
+/// # Examples
+///
+/// ```
+/// use ip_info::structured_fields::parse_list;
+///
+/// let list = parse_list(r#""Chromium";v="124", "Not-A.Brand";v="99""#).unwrap();
+/// assert_eq!(list.len(), 2);
+///
+/// assert!(parse_list("a, ").is_err());
+/// ```
+pub fn parse_list(input: &str) -> Result<List, ParseError> {
+    Parser::new(input).top_level(Parser::list)
+}
+
+/// Parses a Dictionary field value.
+///
+/// A repeated key overwrites the earlier value but keeps its position.
+pub fn parse_dictionary(input: &str) -> Result<Dictionary, ParseError> {
+    Parser::new(input).top_level(Parser::dictionary)
+}
+
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+fn is_key_char(c: u8) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || b"_-.*".contains(&c)
+}
+
+fn insert_ordered<T>(entries: &mut Vec<(String, T)>, key: String, value: T) {
+    match entries.iter_mut().find(|(k, _)| *k == key) {
+        Some(entry) => entry.1 = value,
+        None => entries.push((key, value)),
+    }
+}
+
+/// A recursive-descent parser following the algorithms in RFC 8941 Section 4.2.
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<u8, ParseError> {
+        let c = self.peek().ok_or(ParseError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(c) => ParseError::UnexpectedCharacter(c as char, self.pos),
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), ParseError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn skip_sp(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_ows(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn top_level<T>(
+        mut self,
+        parse: fn(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.skip_sp();
+        let value = parse(&mut self)?;
+        self.skip_sp();
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(self.unexpected()),
+        }
+    }
+
+    /// Parses the separator after a List or Dictionary member.
+    ///
+    /// Returns `false` at the end of input.
+    fn member_separator(&mut self) -> Result<bool, ParseError> {
+        self.skip_ows();
+        if self.peek().is_none() {
+            return Ok(false);
+        }
+        self.expect(b',')?;
+        self.skip_ows();
+        if self.peek().is_none() {
+            // A trailing comma is not allowed.
+            return Err(ParseError::UnexpectedEnd);
+        }
+        Ok(true)
+    }
+
+    fn list(&mut self) -> Result<List, ParseError> {
+        let mut members = Vec::new();
+        if self.peek().is_none() {
+            return Ok(members);
+        }
+        loop {
+            members.push(self.member()?);
+            if !self.member_separator()? {
+                return Ok(members);
+            }
+        }
+    }
+
+    fn dictionary(&mut self) -> Result<Dictionary, ParseError> {
+        let mut entries = Vec::new();
+        if self.peek().is_none() {
+            return Ok(entries);
+        }
+        loop {
+            let key = self.key()?;
+            let member = if self.peek() == Some(b'=') {
+                self.pos += 1;
+                self.member()?
+            } else {
+                Member::Item(Item {
+                    bare_item: BareItem::Boolean(true),
+                    params: self.params()?,
+                })
+            };
+            insert_ordered(&mut entries, key, member);
+            if !self.member_separator()? {
+                return Ok(entries);
+            }
+        }
+    }
+
+    fn member(&mut self) -> Result<Member, ParseError> {
+        if self.peek() == Some(b'(') {
+            self.inner_list().map(Member::InnerList)
+        } else {
+            self.item().map(Member::Item)
+        }
+    }
+
+    fn inner_list(&mut self) -> Result<InnerList, ParseError> {
+        self.expect(b'(')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_sp();
+            if self.peek() == Some(b')') {
+                self.pos += 1;
+                let params = self.params()?;
+                return Ok(InnerList { items, params });
+            }
+            items.push(self.item()?);
+            if !matches!(self.peek(), Some(b' ' | b')')) {
+                return Err(self.unexpected());
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, ParseError> {
+        let bare_item = self.bare_item()?;
+        let params = self.params()?;
+        Ok(Item { bare_item, params })
+    }
+
+    fn params(&mut self) -> Result<Parameters, ParseError> {
+        let mut params = Vec::new();
+        while self.peek() == Some(b';') {
+            self.pos += 1;
+            self.skip_sp();
+            let key = self.key()?;
+            let value = if self.peek() == Some(b'=') {
+                self.pos += 1;
+                self.bare_item()?
+            } else {
+                BareItem::Boolean(true)
+            };
+            insert_ordered(&mut params, key, value);
+        }
+        Ok(params)
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        match self.peek() {
+            Some(c) if c.is_ascii_lowercase() || c == b'*' => self.pos += 1,
+            _ => return Err(self.unexpected()),
+        }
+        while self.peek().is_some_and(is_key_char) {
+            self.pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+
+    fn bare_item(&mut self) -> Result<BareItem, ParseError> {
+        match self.peek() {
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'"') => self.string().map(BareItem::String),
+            Some(b'*' | b'A'..=b'Z' | b'a'..=b'z') => Ok(BareItem::Token(self.token())),
+            Some(b':') => self.byte_sequence().map(BareItem::ByteSequence),
+            Some(b'?') => self.boolean().map(BareItem::Boolean),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn number(&mut self) -> Result<BareItem, ParseError> {
+        let start = self.pos;
+        let negative = self.peek() == Some(b'-');
+        if negative {
+            self.pos += 1;
+        }
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return Err(self.unexpected());
+        }
+
+        let digits_start = self.pos;
+        let mut dot = None;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                self.pos += 1;
+            } else if c == b'.' && dot.is_none() {
+                if self.pos - digits_start > 12 {
+                    return Err(ParseError::InvalidNumber(start));
+                }
+                dot = Some(self.pos);
+                self.pos += 1;
+            } else {
+                break;
+            }
+            let len = self.pos - digits_start;
+            if (dot.is_none() && len > 15) || len > 16 {
+                return Err(ParseError::InvalidNumber(start));
+            }
+        }
+
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+        match dot {
+            None => text
+                .parse()
+                .map(BareItem::Integer)
+                .map_err(|_| ParseError::InvalidNumber(start)),
+            Some(dot) => {
+                let fraction = self.pos - dot - 1;
+                if fraction == 0 || fraction > 3 {
+                    return Err(ParseError::InvalidNumber(start));
+                }
+                text.parse()
+                    .map(BareItem::Decimal)
+                    .map_err(|_| ParseError::InvalidNumber(start))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.expect(b'"')?;
+        let mut out = String::new();
+        loop {
+            let pos = self.pos;
+            match self.next()? {
+                b'\\' => match self.next()? {
+                    c @ (b'"' | b'\\') => out.push(c as char),
+                    c => return Err(ParseError::UnexpectedCharacter(c as char, pos + 1)),
+                },
+                b'"' => return Ok(out),
+                c @ 0x20..=0x7e => out.push(c as char),
+                c => return Err(ParseError::UnexpectedCharacter(c as char, pos)),
+            }
+        }
+    }
+
+    fn token(&mut self) -> String {
+        let start = self.pos;
+        self.pos += 1;
+        while self
+            .peek()
+            .is_some_and(|c| is_tchar(c) || c == b':' || c == b'/')
+        {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.input[start..self.pos]).into_owned()
+    }
+
+    fn byte_sequence(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.expect(b':')?;
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            match c {
+                b':' => {
+                    let encoded = &self.input[start..self.pos];
+                    self.pos += 1;
+                    return BASE64_LENIENT
+                        .decode(encoded)
+                        .map_err(|_| ParseError::InvalidByteSequence(start));
+                }
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' | b'=' => self.pos += 1,
+                _ => return Err(self.unexpected()),
+            }
+        }
+        Err(ParseError::UnexpectedEnd)
+    }
+
+    fn boolean(&mut self) -> Result<bool, ParseError> {
+        self.expect(b'?')?;
+        match self.peek() {
+            Some(b'1') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(b'0') => {
+                self.pos += 1;
+                Ok(false)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+impl fmt::Display for BareItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BareItem::Integer(i) => write!(f, "{i}"),
+            BareItem::Decimal(d) => {
+                let s = format!("{d:.3}");
+                let s = s.trim_end_matches('0');
+                if s.ends_with('.') {
+                    write!(f, "{s}0")
+                } else {
+                    write!(f, "{s}")
+                }
+            }
+            BareItem::String(s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{c}")?;
+                }
+                f.write_str("\"")
+            }
+            BareItem::Token(t) => f.write_str(t),
+            BareItem::ByteSequence(b) => write!(f, ":{}:", general_purpose::STANDARD.encode(b)),
+            BareItem::Boolean(b) => f.write_str(if *b { "?1" } else { "?0" }),
+        }
+    }
+}
+
+fn fmt_params(params: &Parameters, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (key, value) in params {
+        write!(f, ";{key}")?;
+        if *value != BareItem::Boolean(true) {
+            write!(f, "={value}")?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bare_item)?;
+        fmt_params(&self.params, f)
+    }
+}
+
+impl fmt::Display for InnerList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(")?;
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{item}")?;
+        }
+        f.write_str(")")?;
+        fmt_params(&self.params, f)
+    }
+}
+
+impl fmt::Display for Member {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Member::Item(item) => write!(f, "{item}"),
+            Member::InnerList(inner_list) => write!(f, "{inner_list}"),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Item(item) => write!(f, "{item}"),
+            Field::List(list) => {
+                for (i, member) in list.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{member}")?;
+                }
+                Ok(())
+            }
+            Field::Dictionary(dictionary) => {
+                for (i, (key, member)) in dictionary.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    f.write_str(key)?;
+                    match member {
+                        Member::Item(Item {
+                            bare_item: BareItem::Boolean(true),
+                            params,
+                        }) => fmt_params(params, f)?,
+                        member => write!(f, "={member}")?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Serialize for BareItem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BareItem::Integer(i) => serializer.serialize_i64(*i),
+            BareItem::Decimal(d) => serializer.serialize_f64(*d),
+            BareItem::String(s) => serializer.serialize_str(s),
+            BareItem::Token(t) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("token", t)?;
+                map.end()
+            }
+            BareItem::ByteSequence(b) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("binary", &general_purpose::STANDARD.encode(b))?;
+                map.end()
+            }
+            BareItem::Boolean(b) => serializer.serialize_bool(*b),
+        }
+    }
+}
+
+/// Serializes ordered key-value pairs as a JSON object, preserving order.
+struct Ordered<'a, T>(&'a [(String, T)]);
+
+impl<T: Serialize> Serialize for Ordered<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Item {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("value", &self.bare_item)?;
+        map.serialize_entry("params", &Ordered(&self.params))?;
+        map.end()
+    }
+}
+
+impl Serialize for InnerList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("items", &self.items)?;
+        map.serialize_entry("params", &Ordered(&self.params))?;
+        map.end()
+    }
+}
+
+impl Serialize for Member {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Member::Item(item) => item.serialize(serializer),
+            Member::InnerList(inner_list) => inner_list.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Field::Item(item) => item.serialize(serializer),
+            Field::List(list) => list.serialize(serializer),
+            Field::Dictionary(dictionary) => Ordered(dictionary).serialize(serializer),
+        }
+    }
+}
+
+/// Returns a reference to the schema of a type.
+fn schema_ref<T: ToSchema>() -> RefOr<Schema> {
+    Ref::from_schema_name(T::name()).into()
+}
+
+/// Returns the schema of parameters, an object of bare items.
+fn params_schema() -> Object {
+    Object::builder()
+        .additional_properties(Some(schema_ref::<BareItem>()))
+        .build()
+}
+
+/// Returns a schema requiring one string property.
+fn wrapped_string(property: &str, description: &str) -> Object {
+    Object::builder()
+        .property(
+            property,
+            Object::builder()
+                .schema_type(Type::String)
+                .description(Some(description)),
+        )
+        .required(property)
+        .build()
+}
+
+impl PartialSchema for BareItem {
+    fn schema() -> RefOr<Schema> {
+        // Integers are also numbers, so the alternatives may overlap
+        AnyOf::builder()
+            .item(Object::with_type(Type::Integer))
+            .item(Object::with_type(Type::Number))
+            .item(Object::with_type(Type::String))
+            .item(wrapped_string("token", "A token"))
+            .item(wrapped_string("binary", "A byte sequence in base64"))
+            .item(Object::with_type(Type::Boolean))
+            .description(Some("A bare item value"))
+            .into()
+    }
+}
+
+impl ToSchema for BareItem {
+    fn name() -> Cow<'static, str> {
+        "StructuredBareItem".into()
+    }
+}
+
+impl PartialSchema for Item {
+    fn schema() -> RefOr<Schema> {
+        Object::builder()
+            .property("value", schema_ref::<BareItem>())
+            .property("params", params_schema())
+            .required("value")
+            .required("params")
+            .description(Some("A bare item with its parameters"))
+            .into()
+    }
+}
+
+impl ToSchema for Item {
+    fn name() -> Cow<'static, str> {
+        "StructuredItem".into()
+    }
+
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((BareItem::name().into(), BareItem::schema()));
+    }
+}
+
+impl PartialSchema for InnerList {
+    fn schema() -> RefOr<Schema> {
+        Object::builder()
+            .property("items", Array::new(schema_ref::<Item>()))
+            .property("params", params_schema())
+            .required("items")
+            .required("params")
+            .description(Some("A parenthesized list of items"))
+            .into()
+    }
+}
+
+impl ToSchema for InnerList {
+    fn name() -> Cow<'static, str> {
+        "StructuredInnerList".into()
+    }
+
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((Item::name().into(), Item::schema()));
+        <Item as ToSchema>::schemas(schemas);
+    }
+}
+
+impl PartialSchema for Member {
+    fn schema() -> RefOr<Schema> {
+        AnyOf::builder()
+            .item(schema_ref::<Item>())
+            .item(schema_ref::<InnerList>())
+            .description(Some("A member of a List or Dictionary"))
+            .into()
+    }
+}
+
+impl ToSchema for Member {
+    fn name() -> Cow<'static, str> {
+        "StructuredMember".into()
+    }
+
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((InnerList::name().into(), InnerList::schema()));
+        <InnerList as ToSchema>::schemas(schemas);
+    }
+}
+
+impl PartialSchema for Field {
+    fn schema() -> RefOr<Schema> {
+        AnyOf::builder()
+            .item(schema_ref::<Item>())
+            .item(Array::new(schema_ref::<Member>()))
+            .item(
+                Object::builder()
+                    .additional_properties(Some(schema_ref::<Member>()))
+                    .build(),
+            )
+            .description(Some("A structured field: an Item, a List or a Dictionary"))
+            .into()
+    }
+}
+
+impl ToSchema for Field {
+    fn name() -> Cow<'static, str> {
+        "StructuredField".into()
+    }
+
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((Member::name().into(), Member::schema()));
+        <Member as ToSchema>::schemas(schemas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(bare_item: BareItem) -> Item {
+        Item {
+            bare_item,
+            params: vec![],
+        }
+    }
+
+    fn roundtrip(field_type: FieldType, input: &str) -> String {
+        parse(field_type, input).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_integer() {
+        assert_eq!(parse_item("42").unwrap(), item(BareItem::Integer(42)));
+        assert_eq!(parse_item("-42").unwrap(), item(BareItem::Integer(-42)));
+        assert_eq!(
+            parse_item("999999999999999").unwrap(),
+            item(BareItem::Integer(999_999_999_999_999))
+        );
+        assert_eq!(
+            parse_item("1000000000000000"),
+            Err(ParseError::InvalidNumber(0))
+        );
+        assert!(parse_item("-").is_err());
+        assert!(parse_item("- 1").is_err());
+    }
+
+    #[test]
+    fn test_decimal() {
+        assert_eq!(parse_item("4.5").unwrap(), item(BareItem::Decimal(4.5)));
+        assert_eq!(
+            parse_item("-0.001").unwrap(),
+            item(BareItem::Decimal(-0.001))
+        );
+        assert_eq!(parse_item("1.2345"), Err(ParseError::InvalidNumber(0)));
+        assert_eq!(parse_item("1."), Err(ParseError::InvalidNumber(0)));
+        assert_eq!(
+            parse_item("1234567890123.0"),
+            Err(ParseError::InvalidNumber(0))
+        );
+        assert!(parse_item("1.2.3").is_err());
+    }
+
+    #[test]
+    fn test_string() {
+        assert_eq!(
+            parse_item(r#""hello \"world\" \\""#).unwrap(),
+            item(BareItem::String(r#"hello "world" \"#.to_string()))
+        );
+        assert_eq!(
+            parse_item(r#""\a""#),
+            Err(ParseError::UnexpectedCharacter('a', 2))
+        );
+        assert_eq!(parse_item(r#""open"#), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            parse_item("\"tab\there\""),
+            Err(ParseError::UnexpectedCharacter('\t', 4))
+        );
+    }
+
+    #[test]
+    fn test_token() {
+        assert_eq!(
+            parse_item("text/html").unwrap(),
+            item(BareItem::Token("text/html".to_string()))
+        );
+        assert_eq!(
+            parse_item("*foo:bar").unwrap(),
+            item(BareItem::Token("*foo:bar".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_byte_sequence() {
+        assert_eq!(
+            parse_item(":aGVsbG8=:").unwrap(),
+            item(BareItem::ByteSequence(b"hello".to_vec()))
+        );
+        assert_eq!(
+            parse_item(":aGVsbG8:").unwrap(),
+            item(BareItem::ByteSequence(b"hello".to_vec()))
+        );
+        assert_eq!(
+            parse_item(":aGVs bG8=:"),
+            Err(ParseError::UnexpectedCharacter(' ', 5))
+        );
+        assert_eq!(parse_item(":aGVsbG8="), Err(ParseError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_boolean() {
+        assert_eq!(parse_item("?1").unwrap(), item(BareItem::Boolean(true)));
+        assert_eq!(parse_item("?0").unwrap(), item(BareItem::Boolean(false)));
+        assert_eq!(
+            parse_item("?2"),
+            Err(ParseError::UnexpectedCharacter('2', 1))
+        );
+    }
+
+    #[test]
+    fn test_item_parameters() {
+        let parsed = parse_item("abc;a=1;b=2;cde_456").unwrap();
+        assert_eq!(
+            parsed.params,
+            vec![
+                ("a".to_string(), BareItem::Integer(1)),
+                ("b".to_string(), BareItem::Integer(2)),
+                ("cde_456".to_string(), BareItem::Boolean(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parameter_overwrite_keeps_position() {
+        let parsed = parse_item("a;x=1;y=2;x=3").unwrap();
+        assert_eq!(
+            parsed.params,
+            vec![
+                ("x".to_string(), BareItem::Integer(3)),
+                ("y".to_string(), BareItem::Integer(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uppercase_key_rejected() {
+        assert_eq!(
+            parse_item("a;A=1"),
+            Err(ParseError::UnexpectedCharacter('A', 2))
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let list = parse_list("sugar, tea, rum").unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(
+            list[1],
+            Member::Item(item(BareItem::Token("tea".to_string())))
+        );
+        assert_eq!(parse_list("").unwrap(), vec![]);
+        assert_eq!(parse_list("a,"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            parse_list("a b"),
+            Err(ParseError::UnexpectedCharacter('b', 2))
+        );
+    }
+
+    #[test]
+    fn test_inner_list() {
+        let list = parse_list(r#"("foo" "bar");lvl=5, ("baz"), ()"#).unwrap();
+        assert_eq!(list.len(), 3);
+        match &list[0] {
+            Member::InnerList(inner) => {
+                assert_eq!(inner.items.len(), 2);
+                assert_eq!(
+                    inner.params,
+                    vec![("lvl".to_string(), BareItem::Integer(5))]
+                );
+            }
+            Member::Item(_) => panic!("expected inner list"),
+        }
+        match &list[2] {
+            Member::InnerList(inner) => assert!(inner.items.is_empty()),
+            Member::Item(_) => panic!("expected inner list"),
+        }
+        assert!(parse_list(r#"("foo""bar")"#).is_err());
+        assert!(parse_list(r#"("foo""#).is_err());
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let dict = parse_dictionary("a=?0, b, c;foo=bar").unwrap();
+        assert_eq!(dict.len(), 3);
+        assert_eq!(dict[0].1, Member::Item(item(BareItem::Boolean(false))));
+        assert_eq!(dict[1].1, Member::Item(item(BareItem::Boolean(true))));
+        assert_eq!(
+            dict[2].1,
+            Member::Item(Item {
+                bare_item: BareItem::Boolean(true),
+                params: vec![("foo".to_string(), BareItem::Token("bar".to_string()))],
+            })
+        );
+    }
+
+    #[test]
+    fn test_dictionary_duplicate_key() {
+        let dict = parse_dictionary("a=1, b=2, a=3").unwrap();
+        assert_eq!(
+            dict[0],
+            ("a".to_string(), Member::Item(item(BareItem::Integer(3))))
+        );
+        assert_eq!(dict.len(), 2);
+    }
+
+    #[test]
+    fn test_surrounding_whitespace() {
+        assert!(parse_list("  a, b  ").is_ok());
+        assert!(parse_list("a ,\tb").is_ok());
+        assert!(parse_list("\ta").is_err());
+    }
+
+    #[test]
+    fn test_serialize_canonical() {
+        assert_eq!(roundtrip(FieldType::Item, "1.500"), "1.5");
+        assert_eq!(roundtrip(FieldType::Item, "1.0"), "1.0");
+        assert_eq!(roundtrip(FieldType::Item, ":aGVsbG8:"), ":aGVsbG8=:");
+        assert_eq!(roundtrip(FieldType::Item, r#""a\"b""#), r#""a\"b""#);
+        assert_eq!(
+            roundtrip(FieldType::List, "a;x=?1 ,  (b  c);y=?0"),
+            "a;x, (b c);y=?0"
+        );
+        assert_eq!(
+            roundtrip(FieldType::Dictionary, "a=?1, b=(1 2), c;p=1"),
+            "a, b=(1 2), c;p=1"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let field = parse(FieldType::Dictionary, r#"sig=:AAE=:, t=tok;q="s""#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&field).unwrap(),
+            r#"{"sig":{"value":{"binary":"AAE="},"params":{}},"t":{"value":{"token":"tok"},"params":{"q":"s"}}}"#
+        );
+    }
+
+    #[test]
+    fn test_known_field_type() {
+        assert_eq!(known_field_type("sec-ch-ua"), Some(FieldType::List));
+        assert_eq!(known_field_type("Sec-CH-UA-Mobile"), Some(FieldType::Item));
+        assert_eq!(known_field_type("accept"), None);
+    }
+}
@@ -0,0 +1,370 @@
+//! Charsets for text responses.
+//!
+//! Handlers produce UTF-8. For clients that cannot handle it, the
+//! [`transcode`] middleware converts plain text and HTML responses into the
+//! charset negotiated from the `charset` parameter of the matching `Accept`
+//! ranges, or from the `Accept-Charset` header.
+//!
+//! HTML can represent any character with a numeric character reference, or
+//! a JavaScript `\uXXXX` escape inside `<script>` elements, where references
+//! are not decoded, so it can always be transcoded. Plain text containing
+//! characters the acceptable charsets cannot represent is answered with
+//! `406 Not Acceptable`.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::transcode::Charset;
+//!
+//! assert_eq!(Charset::Iso8859_1.encode("café"), Some(b"caf\xe9".to_vec()));
+//! assert_eq!(Charset::UsAscii.encode("café"), None);
+//! assert_eq!(Charset::UsAscii.encode_html("café"), b"caf&#xe9;".to_vec());
+//! assert_eq!(
+//!     Charset::UsAscii.encode_html(r#"<script>alert("é")</script>"#),
+//!     br#"<script>alert("\u00e9")</script>"#.to_vec()
+//! );
+//! ```
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::content_negotiation::{
+    charset_ranges, parse_accept, parse_accept_charset, rank_charsets, MediaType,
+};
+use crate::negotiated::{NegotiationOptions, NotAcceptable, Vary};
+
+/// Media types the [`transcode`] middleware converts.
+const TEXT_TYPES: &[&str] = &["text/plain", "text/html"];
+
+lazy_static! {
+    static ref META_CHARSET: Regex = Regex::new(r#"(?i)<meta\s+charset="?utf-8"?"#).unwrap();
+    /// The content of `<script>` elements
+    static ref SCRIPT: Regex = Regex::new(r"(?is)<script\b[^>]*>(.*?)</script\s*>").unwrap();
+}
+
+/// A charset supported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Iso8859_1,
+    UsAscii,
+}
+
+impl Charset {
+    /// All charsets, in order of server preference.
+    pub const ALL: [Charset; 3] = [Charset::Utf8, Charset::Iso8859_1, Charset::UsAscii];
+
+    /// Returns the preferred MIME name of the charset.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Iso8859_1 => "iso-8859-1",
+            Charset::UsAscii => "us-ascii",
+        }
+    }
+
+    /// Returns the charsets acceptable for a response of the given media type,
+    /// most preferred first.
+    ///
+    /// Charsets requested by the `Accept` ranges including the media type
+    /// take precedence over the `Accept-Charset` header. Without either,
+    /// every charset is acceptable.
+    pub fn ranked(headers: &HeaderMap, media_type: &MediaType) -> Vec<Charset> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+        };
+
+        let mut ranges = header(header::ACCEPT)
+            .map(|accept| charset_ranges(&parse_accept(accept), media_type))
+            .unwrap_or_default();
+        if ranges.is_empty() {
+            ranges = header(header::ACCEPT_CHARSET)
+                .map(parse_accept_charset)
+                .unwrap_or_default();
+        }
+
+        let available = Charset::ALL.map(Charset::as_str);
+        rank_charsets(&ranges, &available)
+            .into_iter()
+            .map(|i| Charset::ALL[i])
+            .collect()
+    }
+
+    fn represents(self, c: char) -> bool {
+        match self {
+            Charset::Utf8 => true,
+            Charset::Iso8859_1 => (c as u32) <= 0xFF,
+            Charset::UsAscii => c.is_ascii(),
+        }
+    }
+
+    /// Encodes text, or returns `None` if it contains a character the charset
+    /// cannot represent.
+    pub fn encode(self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            Charset::Utf8 => Some(text.as_bytes().to_vec()),
+            _ => text
+                .chars()
+                .map(|c| self.represents(c).then_some(c as u8))
+                .collect(),
+        }
+    }
+
+    /// Encodes HTML, replacing characters the charset cannot represent with
+    /// numeric character references, or with JavaScript escapes inside
+    /// `<script>` elements.
+    pub fn encode_html(self, html: &str) -> Vec<u8> {
+        if self == Charset::Utf8 {
+            return html.as_bytes().to_vec();
+        }
+
+        let mut encoded = Vec::with_capacity(html.len());
+        let mut last = 0;
+        for captures in SCRIPT.captures_iter(html) {
+            let script = captures.get(1).unwrap();
+            self.encode_escaped(&html[last..script.start()], &mut encoded, |c| {
+                format!("&#x{:x};", c as u32)
+            });
+            self.encode_escaped(script.as_str(), &mut encoded, |c| {
+                let mut units = [0; 2];
+                c.encode_utf16(&mut units)
+                    .iter()
+                    .map(|unit| format!("\\u{unit:04x}"))
+                    .collect()
+            });
+            last = script.end();
+        }
+        self.encode_escaped(&html[last..], &mut encoded, |c| {
+            format!("&#x{:x};", c as u32)
+        });
+        encoded
+    }
+
+    /// Encodes text, replacing characters the charset cannot represent with
+    /// their `escape`.
+    fn encode_escaped(self, text: &str, encoded: &mut Vec<u8>, escape: impl Fn(char) -> String) {
+        for c in text.chars() {
+            if self.represents(c) {
+                encoded.push(c as u8);
+            } else {
+                encoded.extend(escape(c).bytes());
+            }
+        }
+    }
+}
+
+/// Returns the media type of a successful UTF-8 text response.
+fn text_media_type(response: &Response) -> Option<MediaType> {
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)?
+        .to_str()
+        .ok()?;
+    let mut media_type = MediaType::try_from(content_type).ok()?;
+    let is_utf8 = media_type
+        .parameters
+        .iter()
+        .any(|(name, value)| name == "charset" && value.eq_ignore_ascii_case("utf-8"));
+    let essence = format!("{}/{}", media_type.main_type, media_type.sub_type);
+    if !is_utf8 || !TEXT_TYPES.contains(&essence.as_str()) {
+        return None;
+    }
+
+    // Other parameters, such as the `version` of Prometheus metrics, are kept
+    media_type.parameters.retain(|(name, _)| name != "charset");
+    Some(media_type)
+}
+
+/// Returns the `Content-Type` of a media type in a charset.
+fn content_type(media_type: &MediaType, charset: Charset) -> String {
+    format!("{media_type}; charset={}", charset.as_str())
+}
+
+/// Middleware converting UTF-8 plain text and HTML responses into the
+/// negotiated charset.
+///
+/// Responds with `406 Not Acceptable` if no acceptable charset can represent
+/// the response, unless [`NegotiationOptions::lenient`] is set, in which case
+/// the response is left in UTF-8. Records `Accept-Charset` in the request's
+/// [`Vary`] set.
+pub async fn transcode(request: Request, next: Next) -> Response {
+    let vary = request
+        .extensions()
+        .get::<Vary>()
+        .cloned()
+        .unwrap_or_default();
+    let options = request
+        .extensions()
+        .get::<NegotiationOptions>()
+        .copied()
+        .unwrap_or_default();
+    let headers = request.headers().clone();
+
+    let response = next.run(request).await;
+    let Some(media_type) = text_media_type(&response) else {
+        return response;
+    };
+
+    vary.add(header::ACCEPT_CHARSET);
+    let ranked = Charset::ranked(&headers, &media_type);
+    if ranked.first() == Some(&Charset::Utf8) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let text = String::from_utf8_lossy(&body);
+
+    let is_html = media_type.sub_type == "html";
+    let encoded = ranked.iter().find_map(|&charset| {
+        if is_html {
+            let html =
+                META_CHARSET.replace(&text, format!("<meta charset=\"{}\"", charset.as_str()));
+            Some((charset, charset.encode_html(&html)))
+        } else {
+            charset.encode(&text).map(|encoded| (charset, encoded))
+        }
+    });
+
+    let (charset, encoded) = match encoded {
+        Some(encoded) => encoded,
+        None if options.lenient => return Response::from_parts(parts, Body::from(body)),
+        None => {
+            let available = Charset::ALL
+                .into_iter()
+                .filter(|charset| charset.encode(&text).is_some())
+                .map(|charset| content_type(&media_type, charset))
+                .collect();
+            return NotAcceptable { available }.into_response();
+        }
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&content_type(&media_type, charset)).unwrap(),
+    );
+    Response::from_parts(parts, Body::from(encoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(accept: &'static str, accept_charset: &'static str) -> Vec<Charset> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(accept));
+        headers.insert(
+            header::ACCEPT_CHARSET,
+            HeaderValue::from_static(accept_charset),
+        );
+        let plain = MediaType::try_from("text/plain").unwrap();
+        Charset::ranked(&headers, &plain)
+    }
+
+    #[test]
+    fn test_ranked_accept_parameter_takes_precedence() {
+        assert_eq!(
+            ranked("text/plain;charset=us-ascii", "iso-8859-1"),
+            vec![Charset::UsAscii]
+        );
+        assert_eq!(
+            ranked("text/html;charset=us-ascii", "iso-8859-1"),
+            vec![Charset::Iso8859_1]
+        );
+    }
+
+    #[test]
+    fn test_ranked_without_preferences() {
+        assert_eq!(
+            Charset::ranked(
+                &HeaderMap::new(),
+                &MediaType::try_from("text/plain").unwrap()
+            ),
+            Charset::ALL.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_encode_latin1() {
+        assert_eq!(Charset::Iso8859_1.encode("ÿ"), Some(vec![0xFF]));
+        assert_eq!(Charset::Iso8859_1.encode("€"), None);
+        assert_eq!(
+            Charset::Iso8859_1.encode_html("1 €"),
+            b"1 &#x20ac;".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_encode_index_page() {
+        use crate::config::Config;
+        use crate::handle_index::{IpResponse, Meta};
+        use crate::negotiated::{Format, SelectedBy};
+        use askama::Template;
+
+        let meta = Meta::new(Format::Html, SelectedBy::Accept);
+        let page = IpResponse::new(
+            &Config::default(),
+            &HeaderMap::new(),
+            "192.0.2.1".to_string(),
+            meta,
+        );
+        let html = page.render().unwrap();
+        let encoded = String::from_utf8(Charset::UsAscii.encode_html(&html)).unwrap();
+
+        // Character references are not decoded in scripts
+        for script in SCRIPT.captures_iter(&encoded) {
+            assert!(!script[1].contains("&#x"), "{}", &script[1]);
+        }
+        assert!(encoded.contains("&#x1f4cb;"));
+    }
+
+    #[test]
+    fn test_encode_script() {
+        assert_eq!(
+            Charset::Iso8859_1.encode_html("<p>€</p><SCRIPT>x = \"€ 😀\";</SCRIPT>"),
+            b"<p>&#x20ac;</p><SCRIPT>x = \"\\u20ac \\ud83d\\ude00\";</SCRIPT>".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_meta_charset_rewritten() {
+        let html = r#"<meta charset="utf-8" />"#;
+        assert_eq!(
+            META_CHARSET.replace(html, "<meta charset=\"us-ascii\""),
+            r#"<meta charset="us-ascii" />"#
+        );
+    }
+
+    #[test]
+    fn test_content_type_keeps_parameters() {
+        let response = (
+            [(
+                header::CONTENT_TYPE,
+                "text/plain; version=0.0.4; charset=utf-8",
+            )],
+            "",
+        )
+            .into_response();
+        let media_type = text_media_type(&response).unwrap();
+        assert_eq!(
+            content_type(&media_type, Charset::UsAscii),
+            "text/plain;version=0.0.4; charset=us-ascii"
+        );
+    }
+}
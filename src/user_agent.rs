@@ -0,0 +1,554 @@
+//! User-Agent parsing into browser, operating system and device.
+//!
+//! Parsing is driven by a rules file in the format of
+//! [uap-core](https://github.com/ua-parser/uap-core)'s `regexes.yaml`. A small
+//! rules file covering common browsers, operating systems and crawlers is
+//! built in; a complete uap-core file can be loaded from disk instead.
+//!
+//! uap-core rules are written for PCRE. Rules using syntax the `regex` crate
+//! does not support (such as look-around) are skipped when loading.
+//!
+//! # Example
+//!
+//! ```
+//! use ip_info::user_agent::{DeviceType, UserAgentParser};
+//!
+//! let parser = UserAgentParser::builtin();
+//! let ua = parser.parse(
+//!     "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0",
+//! );
+//!
+//! assert_eq!(ua.browser.family, "Firefox");
+//! assert_eq!(ua.browser.version.as_deref(), Some("128.0"));
+//! assert_eq!(ua.os.family, "Windows");
+//! assert_eq!(ua.device.kind, DeviceType::Desktop);
+//! assert!(!ua.is_bot);
+//! ```
+
+use std::{fmt, fs, path::Path};
+
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+const BUILTIN_REGEXES: &str = include_str!("user_agent_regexes.yaml");
+
+/// Family reported when no rule matches, as in uap-core.
+const OTHER: &str = "Other";
+
+/// Device family uap-core assigns to crawlers.
+const SPIDER: &str = "Spider";
+
+/// Errors that can occur when loading User-Agent parsing rules.
+#[derive(Error, Debug)]
+pub enum RulesError {
+    /// The rules file could not be read
+    #[error("failed to read rules file: {0}")]
+    Io(#[from] std::io::Error),
+    /// The rules file is not valid uap-core YAML
+    #[error("invalid rules file: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+}
+
+#[derive(Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    user_agent_parsers: Vec<UserAgentRule>,
+    #[serde(default)]
+    os_parsers: Vec<OsRule>,
+    #[serde(default)]
+    device_parsers: Vec<DeviceRule>,
+}
+
+#[derive(Deserialize)]
+struct UserAgentRule {
+    regex: String,
+    family_replacement: Option<String>,
+    v1_replacement: Option<String>,
+    v2_replacement: Option<String>,
+    v3_replacement: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OsRule {
+    regex: String,
+    os_replacement: Option<String>,
+    os_v1_replacement: Option<String>,
+    os_v2_replacement: Option<String>,
+    os_v3_replacement: Option<String>,
+    os_v4_replacement: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeviceRule {
+    regex: String,
+    regex_flag: Option<String>,
+    device_replacement: Option<String>,
+    brand_replacement: Option<String>,
+    model_replacement: Option<String>,
+}
+
+/// A compiled rule: the regex plus replacements for each output field.
+///
+/// A replacement of `None` takes the capture group at the field's default
+/// position instead.
+#[derive(Debug, Clone)]
+struct Matcher {
+    regex: Regex,
+    replacements: Vec<Option<String>>,
+}
+
+impl Matcher {
+    fn new(regex: &str, case_insensitive: bool, replacements: Vec<Option<String>>) -> Option<Self> {
+        match RegexBuilder::new(regex)
+            .case_insensitive(case_insensitive)
+            .build()
+        {
+            Ok(regex) => Some(Matcher {
+                regex,
+                replacements,
+            }),
+            Err(e) => {
+                tracing::debug!(message = "skipping user agent rule", regex, error = %e);
+                None
+            }
+        }
+    }
+
+    /// Returns one value per replacement if the regex matches `ua`.
+    ///
+    /// Field `i` defaults to capture group `i + 1`.
+    fn apply(&self, ua: &str) -> Option<Vec<Option<String>>> {
+        let captures = self.regex.captures(ua)?;
+        let fields = self
+            .replacements
+            .iter()
+            .enumerate()
+            .map(|(i, replacement)| match replacement {
+                Some(replacement) => Some(expand(replacement, &captures)),
+                None => captures.get(i + 1).map(|m| m.as_str().to_string()),
+            })
+            .map(|field| field.filter(|s| !s.is_empty()))
+            .collect();
+
+        Some(fields)
+    }
+}
+
+/// Substitutes `$1`..`$9` in a replacement with the corresponding capture.
+fn expand(replacement: &str, captures: &Captures) -> String {
+    let mut out = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek().and_then(|d| d.to_digit(10))) {
+            ('$', Some(group)) => {
+                chars.next();
+                if let Some(m) = captures.get(group as usize) {
+                    out.push_str(m.as_str());
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out.trim().to_string()
+}
+
+fn join_version(parts: &[Option<String>]) -> Option<String> {
+    let parts: Vec<&str> = parts.iter().map_while(|p| p.as_deref()).collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("."))
+    }
+}
+
+/// A browser or operating system family with its version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Software {
+    /// The family name (e.g. "Firefox", "Windows"), or "Other" if unknown
+    pub family: String,
+    /// The dotted version (e.g. "128.0"), if known
+    pub version: Option<String>,
+}
+
+impl Software {
+    fn other() -> Self {
+        Software {
+            family: OTHER.to_string(),
+            version: None,
+        }
+    }
+
+    fn from_fields(fields: Vec<Option<String>>) -> Self {
+        let mut fields = fields.into_iter();
+        let family = fields.next().flatten();
+        let version: Vec<Option<String>> = fields.collect();
+
+        match family {
+            Some(family) => Software {
+                family,
+                version: join_version(&version),
+            },
+            None => Software::other(),
+        }
+    }
+}
+
+impl fmt::Display for Software {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} {}", self.family, version),
+            None => write!(f, "{}", self.family),
+        }
+    }
+}
+
+/// The broad category of a client device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceType {
+    Desktop,
+    Mobile,
+    Tablet,
+    Bot,
+    Other,
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DeviceType::Desktop => "desktop",
+            DeviceType::Mobile => "mobile",
+            DeviceType::Tablet => "tablet",
+            DeviceType::Bot => "bot",
+            DeviceType::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A client device as identified by the device rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Device {
+    /// The device family (e.g. "iPhone", "Mac"), or "Other" if unknown
+    pub family: String,
+    /// The manufacturer, if known
+    pub brand: Option<String>,
+    /// The model, if known
+    pub model: Option<String>,
+    /// The device category
+    #[serde(rename = "type")]
+    pub kind: DeviceType,
+}
+
+/// The result of parsing a User-Agent string.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct UserAgentInfo {
+    pub browser: Software,
+    pub os: Software,
+    pub device: Device,
+    /// Whether the client identifies as a crawler or other automated agent
+    pub is_bot: bool,
+}
+
+/// Parses User-Agent strings using uap-core compatible rules.
+#[derive(Debug, Clone)]
+pub struct UserAgentParser {
+    user_agent: Vec<Matcher>,
+    os: Vec<Matcher>,
+    device: Vec<Matcher>,
+}
+
+impl UserAgentParser {
+    /// Returns a parser using the built-in rules.
+    pub fn builtin() -> Self {
+        Self::from_yaml(BUILTIN_REGEXES).expect("built-in user agent rules are valid")
+    }
+
+    /// Loads a parser from a uap-core `regexes.yaml` file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, RulesError> {
+        Self::from_yaml(&fs::read_to_string(path)?)
+    }
+
+    /// Builds a parser from the contents of a uap-core `regexes.yaml` file.
+    ///
+    /// Rules whose regex cannot be compiled are skipped.
+    pub fn from_yaml(yaml: &str) -> Result<Self, RulesError> {
+        let rules: RulesFile = serde_yaml::from_str(yaml)?;
+
+        let user_agent = rules
+            .user_agent_parsers
+            .into_iter()
+            .filter_map(|r| {
+                let replacements = vec![
+                    r.family_replacement,
+                    r.v1_replacement,
+                    r.v2_replacement,
+                    r.v3_replacement,
+                ];
+                Matcher::new(&r.regex, false, replacements)
+            })
+            .collect();
+
+        let os = rules
+            .os_parsers
+            .into_iter()
+            .filter_map(|r| {
+                let replacements = vec![
+                    r.os_replacement,
+                    r.os_v1_replacement,
+                    r.os_v2_replacement,
+                    r.os_v3_replacement,
+                    r.os_v4_replacement,
+                ];
+                Matcher::new(&r.regex, false, replacements)
+            })
+            .collect();
+
+        let device = rules
+            .device_parsers
+            .into_iter()
+            .filter_map(|r| {
+                let case_insensitive = r.regex_flag.as_deref() == Some("i");
+                // The brand has no default capture group, unlike family and model.
+                let brand = Some(r.brand_replacement.unwrap_or_default());
+                let model = r.model_replacement.or_else(|| Some("$1".to_string()));
+                let replacements = vec![r.device_replacement, brand, model];
+                Matcher::new(&r.regex, case_insensitive, replacements)
+            })
+            .collect();
+
+        Ok(UserAgentParser {
+            user_agent,
+            os,
+            device,
+        })
+    }
+
+    fn first_match(matchers: &[Matcher], ua: &str) -> Option<Vec<Option<String>>> {
+        matchers.iter().find_map(|m| m.apply(ua))
+    }
+
+    /// Parses a User-Agent string.
+    pub fn parse(&self, ua: &str) -> UserAgentInfo {
+        let browser = Self::first_match(&self.user_agent, ua)
+            .map(Software::from_fields)
+            .unwrap_or_else(Software::other);
+        let os = Self::first_match(&self.os, ua)
+            .map(Software::from_fields)
+            .unwrap_or_else(Software::other);
+
+        let (family, brand, model) = match Self::first_match(&self.device, ua) {
+            Some(fields) => {
+                let mut fields = fields.into_iter();
+                (
+                    fields.next().flatten(),
+                    fields.next().flatten(),
+                    fields.next().flatten(),
+                )
+            }
+            None => (None, None, None),
+        };
+        let family = family.unwrap_or_else(|| OTHER.to_string());
+
+        let is_bot = family == SPIDER;
+        let kind = device_type(ua, &os, is_bot);
+
+        UserAgentInfo {
+            browser,
+            os,
+            device: Device {
+                family,
+                brand,
+                model,
+                kind,
+            },
+            is_bot,
+        }
+    }
+}
+
+impl Default for UserAgentParser {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+/// Classifies the device, since uap-core rules only identify device families.
+fn device_type(ua: &str, os: &Software, is_bot: bool) -> DeviceType {
+    if is_bot {
+        return DeviceType::Bot;
+    }
+
+    let android = os.family == "Android";
+    if ua.contains("iPad") || ua.contains("Tablet") || ua.contains("Kindle") {
+        return DeviceType::Tablet;
+    }
+    if ua.contains("Mobi") || ua.contains("iPhone") || ua.contains("iPod") {
+        return DeviceType::Mobile;
+    }
+    // Android tablets omit the "Mobile" token phones send.
+    if android {
+        return DeviceType::Tablet;
+    }
+
+    match os.family.as_str() {
+        "Windows" | "Mac OS X" | "Linux" | "Chrome OS" | "Ubuntu" | "Fedora" | "Debian"
+        | "FreeBSD" | "OpenBSD" | "NetBSD" => DeviceType::Desktop,
+        _ => DeviceType::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(ua: &str) -> UserAgentInfo {
+        UserAgentParser::builtin().parse(ua)
+    }
+
+    #[test]
+    fn test_builtin_rules_all_compile() {
+        let rules: RulesFile = serde_yaml::from_str(BUILTIN_REGEXES).unwrap();
+        let parser = UserAgentParser::builtin();
+        assert_eq!(parser.user_agent.len(), rules.user_agent_parsers.len());
+        assert_eq!(parser.os.len(), rules.os_parsers.len());
+        assert_eq!(parser.device.len(), rules.device_parsers.len());
+    }
+
+    #[test]
+    fn test_chrome_windows() {
+        let ua = parse("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36");
+        assert_eq!(ua.browser.family, "Chrome");
+        assert_eq!(ua.browser.version.as_deref(), Some("126.0.0"));
+        assert_eq!(ua.os.family, "Windows");
+        assert_eq!(ua.os.version.as_deref(), Some("10"));
+        assert_eq!(ua.device.kind, DeviceType::Desktop);
+    }
+
+    #[test]
+    fn test_edge_is_not_chrome() {
+        let ua = parse("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.2592.56");
+        assert_eq!(ua.browser.family, "Edge");
+        assert_eq!(ua.browser.version.as_deref(), Some("126.0.2592"));
+    }
+
+    #[test]
+    fn test_safari_macos() {
+        let ua = parse("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15");
+        assert_eq!(ua.browser.family, "Safari");
+        assert_eq!(ua.browser.version.as_deref(), Some("17.5"));
+        assert_eq!(ua.os.family, "Mac OS X");
+        assert_eq!(ua.os.version.as_deref(), Some("10.15.7"));
+        assert_eq!(ua.device.family, "Mac");
+        assert_eq!(ua.device.brand.as_deref(), Some("Apple"));
+        assert_eq!(ua.device.kind, DeviceType::Desktop);
+    }
+
+    #[test]
+    fn test_iphone() {
+        let ua = parse("Mozilla/5.0 (iPhone; CPU iPhone OS 17_5_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1");
+        assert_eq!(ua.browser.family, "Mobile Safari");
+        assert_eq!(ua.os.family, "iOS");
+        assert_eq!(ua.os.version.as_deref(), Some("17.5.1"));
+        assert_eq!(ua.device.family, "iPhone");
+        assert_eq!(ua.device.model.as_deref(), Some("iPhone"));
+        assert_eq!(ua.device.kind, DeviceType::Mobile);
+    }
+
+    #[test]
+    fn test_ipad_is_tablet() {
+        let ua = parse("Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1");
+        assert_eq!(ua.os.family, "iOS");
+        assert_eq!(ua.os.version.as_deref(), Some("16.6"));
+        assert_eq!(ua.device.kind, DeviceType::Tablet);
+    }
+
+    #[test]
+    fn test_android_phone() {
+        let ua = parse("Mozilla/5.0 (Linux; Android 14; Pixel 8 Build/UQ1A.240205.004) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.6478.71 Mobile Safari/537.36");
+        assert_eq!(ua.browser.family, "Chrome Mobile");
+        assert_eq!(ua.os.family, "Android");
+        assert_eq!(ua.os.version.as_deref(), Some("14"));
+        assert_eq!(ua.device.family, "Pixel 8");
+        assert_eq!(ua.device.kind, DeviceType::Mobile);
+    }
+
+    #[test]
+    fn test_android_tablet() {
+        let ua = parse("Mozilla/5.0 (Linux; Android 13; SM-X700) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36");
+        assert_eq!(ua.device.family, "SM-X700");
+        assert_eq!(ua.device.kind, DeviceType::Tablet);
+    }
+
+    #[test]
+    fn test_curl() {
+        let ua = parse("curl/8.5.0");
+        assert_eq!(ua.browser.family, "curl");
+        assert_eq!(ua.browser.version.as_deref(), Some("8.5.0"));
+        assert_eq!(ua.os.family, "Other");
+        assert_eq!(ua.device.kind, DeviceType::Other);
+        assert!(!ua.is_bot);
+    }
+
+    #[test]
+    fn test_googlebot() {
+        let ua = parse("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)");
+        assert_eq!(ua.browser.family, "Googlebot");
+        assert_eq!(ua.browser.version.as_deref(), Some("2.1"));
+        assert_eq!(ua.device.family, "Spider");
+        assert_eq!(ua.device.kind, DeviceType::Bot);
+        assert!(ua.is_bot);
+    }
+
+    #[test]
+    fn test_unknown() {
+        let ua = parse("something else entirely");
+        assert_eq!(ua.browser, Software::other());
+        assert_eq!(ua.os, Software::other());
+        assert_eq!(ua.device.family, "Other");
+    }
+
+    #[test]
+    fn test_custom_rules_with_unsupported_regex_skipped() {
+        let parser = UserAgentParser::from_yaml(
+            r#"
+user_agent_parsers:
+  - regex: '(Foo)(?!Bar)/(\d+)'
+  - regex: '(Foo)/(\d+)'
+    family_replacement: 'Foo Browser $1'
+"#,
+        )
+        .unwrap();
+        assert_eq!(parser.user_agent.len(), 1);
+
+        let ua = parser.parse("Foo/3");
+        assert_eq!(ua.browser.family, "Foo Browser Foo");
+        assert_eq!(ua.browser.version.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_device_regex_flag() {
+        let parser = UserAgentParser::from_yaml(
+            r#"
+device_parsers:
+  - regex: 'widget (\w+)'
+    regex_flag: 'i'
+    brand_replacement: 'Acme'
+"#,
+        )
+        .unwrap();
+        let ua = parser.parse("WIDGET Pro");
+        assert_eq!(ua.device.family, "Pro");
+        assert_eq!(ua.device.brand.as_deref(), Some("Acme"));
+        assert_eq!(ua.device.model.as_deref(), Some("Pro"));
+    }
+
+    #[test]
+    fn test_invalid_yaml() {
+        assert!(UserAgentParser::from_yaml("user_agent_parsers: 3").is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! WebSocket measuring the application-level round-trip time.
+//!
+//! On connect, `/ws` sends the client's [`IpInfo`]. It then sends a `ping`
+//! every second; each `pong` the client echoes back is timed, and answered
+//! with the [`LatencyReport`] of the connection so far. Clients can also
+//! send their own `ping` to time the server, which answers with a `pong`.
+//!
+//! Protocol-level pings are not used, as browsers do not expose them to
+//! scripts. Messages are JSON text frames with a `type`:
+//!
+//! ```text
+//! <- {"type":"info","info":{"schema_version":1,"ip":"192.0.2.1",...}}
+//! <- {"type":"ping","seq":1}
+//! -> {"type":"pong","seq":1}
+//! <- {"type":"stats","samples":1,"last_ms":12.5,"min_ms":12.5,...}
+//! -> {"type":"ping","seq":7}
+//! <- {"type":"pong","seq":7}
+//! ```
+//!
+//! Sessions end after [`MAX_PINGS`] pings, or earlier when the client leaves
+//! several pings unanswered; the server then closes the connection, and
+//! clients wanting more samples reconnect.
+
+use std::{collections::VecDeque, net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, State,
+    },
+    http::HeaderMap,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+use crate::api_v1::{self, IpInfo};
+use crate::config::Config;
+use crate::handle_index::real_ip;
+
+/// Time between two pings of the server.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pings sent before the session ends, bounding it to five minutes.
+pub const MAX_PINGS: u64 = 300;
+
+/// Unanswered pings after which the connection is closed.
+const MAX_PENDING: usize = 10;
+
+/// Largest message accepted from clients.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Round-trip time statistics of a connection.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples: u64,
+    last: Option<Duration>,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+    /// Smoothed jitter in milliseconds
+    jitter: f64,
+}
+
+impl LatencyStats {
+    /// Records a round-trip time.
+    ///
+    /// Jitter is estimated as in RTP (RFC 3550 Section 6.4.1): the mean
+    /// deviation of consecutive round-trip times, smoothed with a gain of
+    /// 1/16.
+    pub fn record(&mut self, rtt: Duration) {
+        if let Some(last) = self.last {
+            let deviation = (millis(rtt) - millis(last)).abs();
+            self.jitter += (deviation - self.jitter) / 16.0;
+            self.min = self.min.min(rtt);
+            self.max = self.max.max(rtt);
+        } else {
+            self.min = rtt;
+            self.max = rtt;
+        }
+        self.samples += 1;
+        self.total += rtt;
+        self.last = Some(rtt);
+    }
+
+    /// Returns the statistics in milliseconds, or `None` before the first
+    /// sample.
+    pub fn report(&self) -> Option<LatencyReport> {
+        let last = self.last?;
+        Some(LatencyReport {
+            samples: self.samples,
+            last_ms: millis(last),
+            min_ms: millis(self.min),
+            avg_ms: millis(self.total) / self.samples as f64,
+            max_ms: millis(self.max),
+            jitter_ms: self.jitter,
+        })
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Round-trip time statistics sent to the client.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct LatencyReport {
+    /// Number of round trips measured
+    pub samples: u64,
+    pub last_ms: f64,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    /// Smoothed variation between consecutive round-trip times
+    pub jitter_ms: f64,
+}
+
+/// Messages sent by the server.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// The client's address and request details
+    Info { info: Box<IpInfo> },
+    /// A ping to be echoed back as a `pong`
+    Ping { seq: u64 },
+    /// The answer to a ping of the client
+    Pong { seq: u64 },
+    /// The statistics after a round trip
+    Stats(LatencyReport),
+    /// A message of the client could not be understood
+    Error { message: String },
+}
+
+/// Messages sent by the client.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Ping { seq: u64 },
+    Pong { seq: u64 },
+}
+
+/// The ping state of a connection.
+#[derive(Debug, Default)]
+pub struct Session {
+    next_seq: u64,
+    /// Sequence numbers and send times of unanswered pings, oldest first
+    pending: VecDeque<(u64, Instant)>,
+    stats: LatencyStats,
+}
+
+impl Session {
+    /// Returns the next ping, or `None` if the session is over: all pings
+    /// were sent, or the client left too many unanswered.
+    pub fn ping(&mut self, now: Instant) -> Option<ServerMessage> {
+        if self.next_seq >= MAX_PINGS || self.pending.len() >= MAX_PENDING {
+            return None;
+        }
+        self.next_seq += 1;
+        self.pending.push_back((self.next_seq, now));
+        Some(ServerMessage::Ping { seq: self.next_seq })
+    }
+
+    /// Handles a text message of the client, returning the answer.
+    ///
+    /// Pongs to unknown or expired pings are ignored. Pings older than the
+    /// one answered are considered lost.
+    pub fn receive(&mut self, text: &str, now: Instant) -> Option<ServerMessage> {
+        match serde_json::from_str(text) {
+            Ok(ClientMessage::Ping { seq }) => Some(ServerMessage::Pong { seq }),
+            Ok(ClientMessage::Pong { seq }) => {
+                let i = self.pending.iter().position(|(s, _)| *s == seq)?;
+                let (_, sent) = self.pending.drain(..=i).next_back()?;
+                self.stats.record(now - sent);
+                self.stats.report().map(ServerMessage::Stats)
+            }
+            Err(e) => Some(ServerMessage::Error {
+                message: e.to_string(),
+            }),
+        }
+    }
+}
+
+/// Opens a WebSocket measuring the round-trip time
+///
+/// Sends the client's details, then pings every second and reports the
+/// round-trip time statistics after each answer. The server closes the
+/// connection after 300 pings.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "websocket",
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 400, description = "Not a WebSocket upgrade request"),
+    )
+)]
+pub async fn handle_ws(
+    ws: WebSocketUpgrade,
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Response {
+    let info = api_v1::ip_info(&config, &headers, real_ip(&headers, addr.ip()));
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| run(socket, info))
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
+
+/// Runs a session until either side closes the connection.
+async fn run(mut socket: WebSocket, info: IpInfo) {
+    if send(
+        &mut socket,
+        &ServerMessage::Info {
+            info: Box::new(info),
+        },
+    )
+    .await
+    .is_err()
+    {
+        return;
+    }
+
+    let mut session = Session::default();
+    let mut interval = tokio::time::interval(PING_INTERVAL);
+    loop {
+        let reply = tokio::select! {
+            _ = interval.tick() => match session.ping(Instant::now()) {
+                Some(ping) => ping,
+                None => {
+                    let close = CloseFrame {
+                        code: close_code::NORMAL,
+                        reason: "session ended".into(),
+                    };
+                    let _ = socket.send(Message::Close(Some(close))).await;
+                    break;
+                }
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match session.receive(&text, Instant::now()) {
+                    Some(reply) => reply,
+                    None => continue,
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Protocol pings are answered by the WebSocket implementation
+                Some(Ok(_)) => continue,
+            },
+        };
+        if send(&mut socket, &reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.report(), None);
+
+        stats.record(Duration::from_millis(10));
+        stats.record(Duration::from_millis(26));
+        stats.record(Duration::from_millis(18));
+        assert_eq!(
+            stats.report(),
+            Some(LatencyReport {
+                samples: 3,
+                last_ms: 18.0,
+                min_ms: 10.0,
+                avg_ms: 18.0,
+                max_ms: 26.0,
+                // 16 / 16, then 1 + (8 - 1) / 16
+                jitter_ms: 1.4375,
+            })
+        );
+    }
+
+    #[test]
+    fn test_pong_times_ping() {
+        let mut session = Session::default();
+        let start = Instant::now();
+        assert_eq!(session.ping(start), Some(ServerMessage::Ping { seq: 1 }));
+        assert_eq!(session.ping(start), Some(ServerMessage::Ping { seq: 2 }));
+
+        let reply = session.receive(
+            r#"{"type":"pong","seq":2}"#,
+            start + Duration::from_millis(40),
+        );
+        let Some(ServerMessage::Stats(report)) = reply else {
+            panic!("expected stats, got {reply:?}");
+        };
+        assert_eq!((report.samples, report.last_ms), (1, 40.0));
+
+        // Ping 1 was lost, and ping 2 is answered
+        assert_eq!(session.receive(r#"{"type":"pong","seq":1}"#, start), None);
+        assert_eq!(session.receive(r#"{"type":"pong","seq":2}"#, start), None);
+    }
+
+    #[test]
+    fn test_client_ping_and_errors() {
+        let mut session = Session::default();
+        let now = Instant::now();
+        assert_eq!(
+            session.receive(r#"{"type":"ping","seq":7}"#, now),
+            Some(ServerMessage::Pong { seq: 7 })
+        );
+        assert!(matches!(
+            session.receive("hello", now),
+            Some(ServerMessage::Error { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unanswered_pings_close() {
+        let mut session = Session::default();
+        let now = Instant::now();
+        for _ in 0..MAX_PENDING {
+            assert!(session.ping(now).is_some());
+        }
+        assert_eq!(session.ping(now), None);
+    }
+
+    #[test]
+    fn test_session_ends_after_max_pings() {
+        let mut session = Session::default();
+        let now = Instant::now();
+        for seq in 1..=MAX_PINGS {
+            assert_eq!(session.ping(now), Some(ServerMessage::Ping { seq }));
+            let pong = format!(r#"{{"type":"pong","seq":{seq}}}"#);
+            assert!(session.receive(&pong, now).is_some());
+        }
+        assert_eq!(session.ping(now), None);
+    }
+
+    #[test]
+    fn test_message_format() {
+        assert_eq!(
+            serde_json::to_string(&ServerMessage::Ping { seq: 3 }).unwrap(),
+            r#"{"type":"ping","seq":3}"#
+        );
+    }
+}
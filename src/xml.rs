@@ -0,0 +1,545 @@
+//! XML representation of serializable values.
+//!
+//! Any [`Serialize`] value can be written as an XML document with
+//! [`to_string()`]. The mapping is fixed, so that every document produced for
+//! a type follows the same schema:
+//!
+//! - struct fields become child elements named after the field
+//! - map entries become `<entry key="...">` elements, since keys such as
+//!   header names are not always valid element names
+//! - sequence elements become `<item>` elements
+//! - booleans, numbers and strings become text, byte arrays base64 text
+//! - unit enum variants become text, other variants an element named after
+//!   the variant
+//! - `None` and unit values are omitted
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//!
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Response {
+//!     ip: String,
+//!     headers: BTreeMap<String, String>,
+//! }
+//!
+//! let response = Response {
+//!     ip: "::1".to_string(),
+//!     headers: BTreeMap::from([("accept".to_string(), "*/*".to_string())]),
+//! };
+//! assert_eq!(
+//!     ip_info::xml::to_string("response", &response).unwrap(),
+//!     r#"<?xml version="1.0" encoding="UTF-8"?>
+//! <response>
+//!   <ip>::1</ip>
+//!   <headers>
+//!     <entry key="accept">*/*</entry>
+//!   </headers>
+//! </response>
+//! "#
+//! );
+//! ```
+
+use std::fmt::{self, Write};
+
+use base64::{engine::general_purpose, Engine};
+use serde::ser::{self, Serialize};
+use thiserror::Error;
+
+/// Errors from [`to_string()`].
+#[derive(Error, Debug, PartialEq)]
+pub enum XmlError {
+    #[error("map keys must be strings, numbers or booleans")]
+    InvalidKey,
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl ser::Error for XmlError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        XmlError::Custom(msg.to_string())
+    }
+}
+
+/// Serializes a value as an XML document with the given root element.
+pub fn to_string<T: Serialize + ?Sized>(root: &str, value: &T) -> Result<String, XmlError> {
+    let node = value.serialize(NodeSerializer)?;
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    write_element(&mut xml, 0, root, None, &node);
+    Ok(xml)
+}
+
+/// The content of an element.
+#[derive(Debug)]
+enum Node {
+    /// Omitted from its parent
+    Absent,
+    Text(String),
+    Children(Vec<Element>),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    /// The `key` attribute of map entries
+    key: Option<String>,
+    node: Node,
+}
+
+fn write_element(xml: &mut String, depth: usize, name: &str, key: Option<&str>, node: &Node) {
+    let indent = "  ".repeat(depth);
+    xml.push_str(&indent);
+    xml.push('<');
+    xml.push_str(name);
+    if let Some(key) = key {
+        write!(xml, " key=\"{}\"", escape(key, true)).unwrap();
+    }
+
+    match node {
+        Node::Absent => xml.push_str("/>\n"),
+        Node::Text(text) => writeln!(xml, ">{}</{name}>", escape(text, false)).unwrap(),
+        Node::Children(children) if children.is_empty() => xml.push_str("/>\n"),
+        Node::Children(children) => {
+            xml.push_str(">\n");
+            for child in children {
+                write_element(
+                    xml,
+                    depth + 1,
+                    &child.name,
+                    child.key.as_deref(),
+                    &child.node,
+                );
+            }
+            writeln!(xml, "{indent}</{name}>").unwrap();
+        }
+    }
+}
+
+/// Escapes markup characters, replacing characters not allowed in XML 1.0
+/// with U+FFFD.
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Serializes values into a [`Node`] tree.
+struct NodeSerializer;
+
+/// Collects the children of a sequence, map or struct.
+struct Children {
+    /// Element wrapping the children, for enum variants
+    variant: Option<&'static str>,
+    children: Vec<Element>,
+    /// Key of the map entry being serialized
+    key: Option<String>,
+}
+
+impl Children {
+    fn new(variant: Option<&'static str>) -> Self {
+        Children {
+            variant,
+            children: Vec::new(),
+            key: None,
+        }
+    }
+
+    fn push(&mut self, name: &str, key: Option<String>, node: Node) {
+        self.children.push(Element {
+            name: name.to_string(),
+            key,
+            node,
+        });
+    }
+
+    fn finish(self) -> Node {
+        let node = Node::Children(self.children);
+        match self.variant {
+            Some(variant) => Node::Children(vec![Element {
+                name: variant.to_string(),
+                key: None,
+                node,
+            }]),
+            None => node,
+        }
+    }
+}
+
+fn text(value: impl ToString) -> Result<Node, XmlError> {
+    Ok(Node::Text(value.to_string()))
+}
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = XmlError;
+    type SerializeSeq = Children;
+    type SerializeTuple = Children;
+    type SerializeTupleStruct = Children;
+    type SerializeTupleVariant = Children;
+    type SerializeMap = Children;
+    type SerializeStruct = Children;
+    type SerializeStructVariant = Children;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, XmlError> {
+        text(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, XmlError> {
+        text(general_purpose::STANDARD.encode(v))
+    }
+
+    fn serialize_none(self) -> Result<Node, XmlError> {
+        Ok(Node::Absent)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, XmlError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, XmlError> {
+        Ok(Node::Absent)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, XmlError> {
+        Ok(Node::Absent)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Node, XmlError> {
+        text(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node, XmlError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, XmlError> {
+        let mut children = Children::new(None);
+        children.push(variant, None, value.serialize(self)?);
+        Ok(children.finish())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Children, XmlError> {
+        Ok(Children::new(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Children, XmlError> {
+        Ok(Children::new(None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Children, XmlError> {
+        Ok(Children::new(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Children, XmlError> {
+        Ok(Children::new(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Children, XmlError> {
+        Ok(Children::new(None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Children, XmlError> {
+        Ok(Children::new(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Children, XmlError> {
+        Ok(Children::new(Some(variant)))
+    }
+}
+
+impl Children {
+    /// Adds a sequence element; absent elements are kept to preserve
+    /// positions.
+    fn push_item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), XmlError> {
+        let node = value.serialize(NodeSerializer)?;
+        self.push("item", None, node);
+        Ok(())
+    }
+
+    /// Adds a struct field, unless it is absent.
+    fn push_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), XmlError> {
+        match value.serialize(NodeSerializer)? {
+            Node::Absent => {}
+            node => self.push(name, None, node),
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Children {
+    type Ok = Node;
+    type Error = XmlError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), XmlError> {
+        self.push_item(value)
+    }
+
+    fn end(self) -> Result<Node, XmlError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for Children {
+    type Ok = Node;
+    type Error = XmlError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), XmlError> {
+        self.push_item(value)
+    }
+
+    fn end(self) -> Result<Node, XmlError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for Children {
+    type Ok = Node;
+    type Error = XmlError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), XmlError> {
+        self.push_item(value)
+    }
+
+    fn end(self) -> Result<Node, XmlError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for Children {
+    type Ok = Node;
+    type Error = XmlError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), XmlError> {
+        self.push_item(value)
+    }
+
+    fn end(self) -> Result<Node, XmlError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeMap for Children {
+    type Ok = Node;
+    type Error = XmlError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), XmlError> {
+        match key.serialize(NodeSerializer)? {
+            Node::Text(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(XmlError::InvalidKey),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), XmlError> {
+        let key = self.key.take();
+        match value.serialize(NodeSerializer)? {
+            Node::Absent => {}
+            node => self.push("entry", key, node),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, XmlError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for Children {
+    type Ok = Node;
+    type Error = XmlError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), XmlError> {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Node, XmlError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for Children {
+    type Ok = Node;
+    type Error = XmlError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), XmlError> {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Node, XmlError> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::*;
+
+    fn body(value: &impl Serialize) -> String {
+        to_string("r", value)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(str::trim)
+            .collect()
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            body(&BTreeMap::from([("a\"<b>", "x & y\u{1}")])),
+            "<r><entry key=\"a&quot;&lt;b&gt;\">x &amp; y\u{FFFD}</entry></r>"
+        );
+    }
+
+    #[test]
+    fn test_optional_fields_omitted() {
+        #[derive(Serialize)]
+        struct S {
+            a: Option<u8>,
+            b: Option<u8>,
+            c: Vec<Option<bool>>,
+        }
+
+        assert_eq!(
+            body(&S {
+                a: None,
+                b: Some(1),
+                c: vec![Some(true), None],
+            }),
+            "<r><b>1</b><c><item>true</item><item/></c></r>"
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        #[derive(Serialize)]
+        #[serde(rename_all = "lowercase")]
+        enum E {
+            Unit,
+            Newtype(u8),
+            Struct { x: u8 },
+        }
+
+        assert_eq!(
+            body(&vec![E::Unit, E::Newtype(1), E::Struct { x: 2 }]),
+            "<r><item>unit</item><item><newtype>1</newtype></item>\
+             <item><struct><x>2</x></struct></item></r>"
+        );
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let map = BTreeMap::from([(vec![1], 1)]);
+        assert_eq!(to_string("r", &map), Err(XmlError::InvalidKey));
+    }
+}